@@ -1,4 +1,4 @@
-use cosmwasm_std::{Coin, StdError, StdResult};
+use cosmwasm_std::{Coin, StdError, StdResult, Uint128};
 
 pub fn assert_sent_sufficient_coin(sent: &[Coin], required: Option<Coin>) -> StdResult<()> {
     if let Some(required_coin) = required {
@@ -20,19 +20,46 @@ pub fn assert_sent_sufficient_coin(sent: &[Coin], required: Option<Coin>) -> Std
     Ok(())
 }
 
+/// assert_only_denom rejects any sent coin that is not of the accepted denom,
+/// so that foreign tokens are never stranded in the contract
+pub fn assert_only_denom(sent: &[Coin], denom: &str) -> StdResult<()> {
+    match sent.iter().find(|coin| coin.denom != denom) {
+        Some(coin) => Err(StdError::generic_err(format!(
+            "Unsupported denom sent: {}",
+            coin.denom
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// checked_add returns a StdError::Overflow instead of trapping on u128 overflow
+pub fn checked_add(a: Uint128, b: Uint128) -> StdResult<Uint128> {
+    a.checked_add(b).map_err(StdError::overflow)
+}
+
+/// checked_mul returns a StdError::Overflow instead of trapping on u128 overflow
+pub fn checked_mul(a: Uint128, b: u64) -> StdResult<Uint128> {
+    a.checked_mul(Uint128::from(b)).map_err(StdError::overflow)
+}
+
+/// checked_sub returns a StdError::Overflow instead of trapping when b > a
+pub fn checked_sub(a: Uint128, b: Uint128) -> StdResult<Uint128> {
+    a.checked_sub(b).map_err(StdError::overflow)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use cosmwasm_std::{coin, coins, StdError};
+    use cosmwasm_std::{coin, coins, OverflowOperation, StdError};
 
     #[test]
     fn assert_sent_sufficient_coin_works() {
-        match assert_sent_sufficient_coin(&vec![], Some(coin(0, "token"))) {
+        match assert_sent_sufficient_coin(&[], Some(coin(0, "token"))) {
             Ok(()) => {}
             Err(e) => panic!("Unexpected error: {:?}", e),
         };
 
-        match assert_sent_sufficient_coin(&vec![], Some(coin(5, "token"))) {
+        match assert_sent_sufficient_coin(&[], Some(coin(5, "token"))) {
             Ok(()) => panic!("Should have raised insufficient funds error"),
             Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Insufficient funds sent"),
             Err(e) => panic!("Unexpected error: {:?}", e),
@@ -55,4 +82,72 @@ mod test {
             Err(e) => panic!("Unexpected error: {:?}", e),
         };
     }
+
+    #[test]
+    fn assert_only_denom_works() {
+        match assert_only_denom(&[], "token") {
+            Ok(()) => {}
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+
+        match assert_only_denom(&coins(10, "token"), "token") {
+            Ok(()) => {}
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+
+        let sent_coins = vec![coin(5, "token"), coin(2, "smokin")];
+        match assert_only_denom(&sent_coins, "token") {
+            Ok(()) => panic!("Should have raised unsupported denom error"),
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "Unsupported denom sent: smokin")
+            }
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+    }
+
+    #[test]
+    fn checked_math_works() {
+        let max = Uint128::from(u128::MAX);
+        assert_eq!(
+            checked_add(Uint128::from(2u128), Uint128::from(3u128)).unwrap(),
+            Uint128::from(5u128)
+        );
+        match checked_add(max, Uint128::from(1u128)) {
+            Ok(_) => panic!("Should have raised overflow error"),
+            Err(StdError::Overflow { source, .. }) => {
+                assert_eq!(source.operation, OverflowOperation::Add);
+                assert_eq!(source.operand1, u128::MAX.to_string());
+                assert_eq!(source.operand2, "1");
+            }
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+
+        assert_eq!(
+            checked_mul(Uint128::from(5u128), 3).unwrap(),
+            Uint128::from(15u128)
+        );
+        match checked_mul(max, 2) {
+            Ok(_) => panic!("Should have raised overflow error"),
+            Err(StdError::Overflow { source, .. }) => {
+                assert_eq!(source.operation, OverflowOperation::Mul);
+                assert_eq!(source.operand1, u128::MAX.to_string());
+                assert_eq!(source.operand2, "2");
+            }
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+
+        assert_eq!(
+            checked_sub(Uint128::from(5u128), Uint128::from(3u128)).unwrap(),
+            Uint128::from(2u128)
+        );
+        match checked_sub(Uint128::from(3u128), Uint128::from(5u128)) {
+            Ok(_) => panic!("Should have raised underflow error"),
+            Err(StdError::Overflow { source, .. }) => {
+                assert_eq!(source.operation, OverflowOperation::Sub);
+                assert_eq!(source.operand1, "3");
+                assert_eq!(source.operand2, "5");
+            }
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        };
+    }
 }
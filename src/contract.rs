@@ -1,144 +1,237 @@
-use crate::coin_helpers::assert_sent_sufficient_coin;
+use crate::coin_helpers::{
+    assert_only_denom, assert_sent_sufficient_coin, checked_add, checked_mul, checked_sub,
+};
 
+use crate::cosmos_msg::{
+    ApprovedResponse, NftChainMsg, NftQuery, NftToken, OwnerResponse, TokenUriResponse,
+};
 use crate::msg::{
-    CreateListingResponse, HandleMsg, InitMsg, ListingResponse, QueryMsg, TokenStakeResponse,
+    AccountResponse, CollectionOfferResponse, CollectionOffersResponse, CreateListingResponse,
+    CreateOfferResponse, DepositResponse, ExecuteMsg, InstantiateMsg, ListingResponse,
+    ListingsResponse, OfferResponse, OffersResponse, QueryMsg, ReserveReveal, StatsResponse,
+    TokenStakeResponse,
 };
 use crate::state::{
-    bank, bank_read, config, config_read, listing, listing_read, Listing, BidStatus, State, Bidder,
+    BidStatus, Bidder, CollectionOffer, Duration, Expiration, Listing, ListingKind,
+    ListingMetadata, Offer, State, Stats, TokenManager, BANK, BIDDERS, COLLECTION_OFFERS,
+    COLLECTION_OFFERS_BY_OFFERER, COLLECTION_OFFERS_BY_PRICE, CONFIG, CREATOR_LISTINGS,
+    ESCROWED_NFTS, LEADING_BIDS, LISTINGS, OFFERS, OFFERS_BY_NFT, OFFERS_BY_OFFERER,
+    SCHEDULED_LISTINGS, STATS,
 };
 use cosmwasm_std::{
-    coin, to_binary, Api, Attribute, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
-    HandleResponse, HandleResult, HumanAddr, InitResponse, InitResult, Querier, StdError,
-    StdResult, Storage, Uint128, MessageInfo, NftMsg,
+    coin, entry_point, to_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Empty, Env, MessageInfo,
+    Order, QueryRequest, Response, StdError, StdResult, Storage, Uint128,
 };
+use cw_storage_plus::Bound;
+use sha2::{Digest, Sha256};
 
 
 // pub const VOTING_TOKEN: &str = "voting_token";
 pub const DEFAULT_END_HEIGHT_BLOCKS: &u64 = &100_800_u64;
+// the same week as DEFAULT_END_HEIGHT_BLOCKS at 6 second blocks
+pub const DEFAULT_END_TIME_SECONDS: &u64 = &604_800_u64;
+const DEFAULT_MIN_DURATION: Duration = Duration { blocks: 600, seconds: 3_600 };
+const DEFAULT_MAX_DURATION: Duration = Duration { blocks: 432_000, seconds: 2_592_000 };
+const DEFAULT_MAX_START_DELAY: Duration = Duration { blocks: 432_000, seconds: 2_592_000 };
 const MIN_STAKE_AMOUNT: u128 = 1;
 const MIN_DESC_LENGTH: usize = 3;
 const MAX_DESC_LENGTH: usize = 64;
-
-pub fn init<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+const MAX_TITLE_LENGTH: usize = 64;
+const MAX_URI_LENGTH: usize = 256;
+const MAX_ATTRIBUTES: usize = 16;
+const MAX_TRAIT_TYPE_LENGTH: usize = 32;
+const MAX_TRAIT_VALUE_LENGTH: usize = 64;
+// page size of the list queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+#[entry_point]
+pub fn instantiate(
+    deps: DepsMut<NftQuery>,
     _env: Env,
     info: MessageInfo,
-    msg: InitMsg,
-) -> InitResult {
+    msg: InstantiateMsg,
+) -> StdResult<Response<NftChainMsg>> {
+    let min_duration = msg.min_duration.unwrap_or(DEFAULT_MIN_DURATION);
+    let max_duration = msg.max_duration.unwrap_or(DEFAULT_MAX_DURATION);
+    validate_durations(&min_duration, &max_duration)?;
+
     let state = State {
         denom: msg.denom.to_string(),
-        owner: deps.api.canonical_address(&info.sender)?,
+        owner: info.sender,
         listing_count: 0,
         staked_tokens: Uint128::zero(),
+        offer_count: 0,
+        collection_offer_count: 0,
+        min_duration,
+        max_duration,
+        max_start_delay: msg.max_start_delay.unwrap_or(DEFAULT_MAX_START_DELAY),
     };
 
-    config(&mut deps.storage).save(&state)?;
+    CONFIG.save(deps.storage, &state)?;
+    STATS.save(deps.storage, &Stats::default())?;
 
-    Ok(InitResponse::default())
+    Ok(Response::default())
 }
 
-pub fn handle<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+#[entry_point]
+pub fn execute(
+    deps: DepsMut<NftQuery>,
     env: Env,
     info: MessageInfo,
-    msg: HandleMsg,
-) -> StdResult<HandleResponse> {
+    msg: ExecuteMsg,
+) -> StdResult<Response<NftChainMsg>> {
     match msg {
-        // HandleMsg::StakeVotingTokens {} => stake_voting_tokens(deps, env, info),
-        HandleMsg::WithdrawTokens { amount } => withdraw_tokens(deps, env, info, amount),
-        HandleMsg::Bid {
+        ExecuteMsg::Deposit {} => deposit(deps, env, info),
+        ExecuteMsg::WithdrawTokens { amount } => withdraw_tokens(deps, env, info, amount),
+        ExecuteMsg::Bid {
+            listing_id,
+            price,
+            refund_surplus,
+        } => bid(deps, env, info, listing_id, price, refund_surplus.unwrap_or(false)),
+        ExecuteMsg::ListFixedPrice {
+            denom,
+            token_id,
+            price,
+            description,
+            metadata,
+        } => list_fixed_price(deps, env, info, denom, token_id, price, description, metadata),
+        ExecuteMsg::Buy { listing_id, price } => buy(deps, env, info, listing_id, price),
+        ExecuteMsg::UpdatePrice { listing_id, price } => {
+            update_price(deps, env, info, listing_id, price)
+        }
+        ExecuteMsg::Delist { listing_id } => delist(deps, env, info, listing_id),
+        ExecuteMsg::UpdateListing {
             listing_id,
-            price
-        } => bid(deps, env, info, listing_id, price),
-        HandleMsg::CloseBid { listing_id } => end_listing(deps, env, info, listing_id),
-        HandleMsg::List {
+            description,
+            minimum_bid,
+            end,
+        } => update_listing(deps, env, info, listing_id, description, minimum_bid, end),
+        ExecuteMsg::CloseBid {
+            listing_id,
+            reserve,
+        } => end_listing(deps, env, info, listing_id, reserve),
+        ExecuteMsg::ClaimNft { listing_id } => claim_nft(deps, env, info, listing_id),
+        ExecuteMsg::ClaimProceeds { listing_id } => claim_proceeds(deps, env, info, listing_id),
+        ExecuteMsg::MakeOffer {
+            denom,
+            token_id,
+            price,
+            expires_at_height,
+        } => make_offer(deps, env, info, denom, token_id, price, expires_at_height),
+        ExecuteMsg::CancelOffer { offer_id } => cancel_offer(deps, env, info, offer_id),
+        ExecuteMsg::AcceptOffer { offer_id } => accept_offer(deps, env, info, offer_id),
+        ExecuteMsg::MakeCollectionOffer {
+            denom,
+            price,
+            quantity,
+            expires_at_height,
+        } => make_collection_offer(deps, env, info, denom, price, quantity, expires_at_height),
+        ExecuteMsg::CancelCollectionOffer { offer_id } => {
+            cancel_collection_offer(deps, env, info, offer_id)
+        }
+        ExecuteMsg::FillCollectionOffer {
+            offer_id,
+            token_ids,
+        } => fill_collection_offer(deps, env, info, offer_id, token_ids),
+        ExecuteMsg::List {
+            denom,
+            token_id,
             minimum_bid,
-            start_height,
-            end_height,
+            start,
+            end,
             description,
+            metadata,
+            reserve_commitment,
         } => create_listing(
             deps,
             env,
             info,
+            ListingKind::Auction,
+            denom,
+            token_id,
             minimum_bid,
-            start_height,
-            end_height,
+            start,
+            end,
             description,
+            metadata,
+            reserve_commitment,
         ),
     }
 }
 
-pub fn stake_voting_tokens<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+// credit the sent funds to the sender's balance so later bids can draw on it
+pub fn deposit(
+    deps: DepsMut<NftQuery>,
     _env: Env,
     info: MessageInfo,
-) -> HandleResult {
-    let sender_address_raw = deps.api.canonical_address(&info.sender)?;
-    let key = sender_address_raw.as_slice();
+) -> StdResult<Response<NftChainMsg>> {
+    let mut token_manager = BANK.may_load(deps.storage, &info.sender)?.unwrap_or_default();
 
-    let mut token_manager = bank_read(&deps.storage).may_load(key)?.unwrap_or_default();
-
-    let mut state = config(&mut deps.storage).load()?;
+    let mut state = CONFIG.load(deps.storage)?;
 
+    assert_only_denom(&info.funds, &state.denom)?;
     assert_sent_sufficient_coin(
-        &info.sent_funds,
+        &info.funds,
         Some(coin(MIN_STAKE_AMOUNT, &state.denom)),
     )?;
     let sent_funds = info
-        .sent_funds
+        .funds
         .iter()
         .find(|coin| coin.denom.eq(&state.denom))
-        .unwrap();
+        .ok_or_else(|| StdError::generic_err("Insufficient funds sent"))?;
+
+    token_manager.token_balance = checked_add(token_manager.token_balance, sent_funds.amount)?;
 
-    token_manager.token_balance =  token_manager.token_balance + sent_funds.amount;
+    state.staked_tokens = checked_add(state.staked_tokens, sent_funds.amount)?;
 
-    state.staked_tokens = state.staked_tokens + sent_funds.amount;
+    CONFIG.save(deps.storage, &state)?;
 
-    config(&mut deps.storage).save(&state)?;
+    BANK.save(deps.storage, &info.sender, &token_manager)?;
 
-    bank(&mut deps.storage).save(key, &token_manager)?;
+    let locked_balance = locked_amount(deps.storage, &info.sender)?;
+    let available_balance = checked_sub(token_manager.token_balance, locked_balance)?;
 
-    Ok(HandleResponse::default())
+    let r = Response::new()
+        .add_attribute("action", "deposit")
+        .add_attribute("amount", sent_funds.amount.to_string())
+        .set_data(to_binary(&DepositResponse {
+            token_balance: token_manager.token_balance,
+            available_balance,
+            locked_balance,
+        })?);
+    Ok(r)
 }
 
 // Withdraw amount if not staked. By default all funds will be withdrawn.
-pub fn withdraw_tokens<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
+pub fn withdraw_tokens(
+    deps: DepsMut<NftQuery>,
+    _env: Env,
     info: MessageInfo,
     amount: Option<Uint128>,
-) -> HandleResult {
-    let sender_address_raw = deps.api.canonical_address(&info.sender)?;
-    let contract_address_raw = deps.api.canonical_address(&env.contract.address)?;
-    let key = sender_address_raw.as_slice();
-
-    if let Some(mut token_manager) = bank_read(&deps.storage).may_load(key)? {
-        let largest_staked = locked_amount(&sender_address_raw, deps);
-        let withdraw_amount = match amount {
-            Some(amount) => Some(amount.u128()),
-            None => Some(token_manager.token_balance.u128()),
-        }
-        .unwrap();
-        if largest_staked + withdraw_amount > token_manager.token_balance.u128() {
+) -> StdResult<Response<NftChainMsg>> {
+    if let Some(mut token_manager) = BANK.may_load(deps.storage, &info.sender)? {
+        let locked_balance = locked_amount(deps.storage, &info.sender)?;
+        let withdraw_amount = amount.unwrap_or(token_manager.token_balance);
+        // the bank module rejects a send of zero coins
+        if withdraw_amount.is_zero() {
+            Err(StdError::generic_err("Nothing to withdraw"))
+        } else if checked_add(locked_balance, withdraw_amount)? > token_manager.token_balance {
             Err(StdError::generic_err(
                 "User is trying to withdraw too many tokens.",
             ))
         } else {
-            let balance = token_manager.token_balance.u128() - withdraw_amount;
-            token_manager.token_balance = Uint128::from(balance);
+            token_manager.token_balance = checked_sub(token_manager.token_balance, withdraw_amount)?;
 
-            bank(&mut deps.storage).save(key, &token_manager)?;
+            BANK.save(deps.storage, &info.sender, &token_manager)?;
 
-            let mut state = config(&mut deps.storage).load()?;
-            let staked_tokens = state.staked_tokens.u128() - withdraw_amount;
-            state.staked_tokens = Uint128::from(staked_tokens);
-            config(&mut deps.storage).save(&state)?;
+            let mut state = CONFIG.load(deps.storage)?;
+            state.staked_tokens = checked_sub(state.staked_tokens, withdraw_amount)?;
+            CONFIG.save(deps.storage, &state)?;
 
             send_tokens(
-                &deps.api,
-                &contract_address_raw,
-                &sender_address_raw,
-                vec![coin(withdraw_amount, &state.denom)],
+                &info.sender,
+                vec![coin(withdraw_amount.u128(), &state.denom)],
                 "approve",
             )
         }
@@ -147,414 +240,1624 @@ pub fn withdraw_tokens<S: Storage, A: Api, Q: Querier>(
     }
 }
 
-/// validate_description returns an error if the description is invalid
+/// validate_description returns an error if the description is invalid.
+/// Lengths count characters so that non-latin descriptions get the same room.
 fn validate_description(description: &str) -> StdResult<()> {
-    if description.len() < MIN_DESC_LENGTH {
+    let length = description.chars().count();
+    if length < MIN_DESC_LENGTH {
         Err(StdError::generic_err("Description too short"))
-    } else if description.len() > MAX_DESC_LENGTH {
+    } else if length > MAX_DESC_LENGTH {
         Err(StdError::generic_err("Description too long"))
     } else {
         Ok(())
     }
 }
 
-/// validate_quorum_percentage returns an error if the quorum_percentage is invalid
-/// (we require 0-100)
-fn validate_quorum_percentage(quorum_percentage: Option<u8>) -> StdResult<()> {
-    if quorum_percentage.is_some() && quorum_percentage.unwrap() > 100 {
-        Err(StdError::generic_err("quorum_percentage must be 0 to 100"))
+/// validate_metadata returns an error if a field of the metadata is empty,
+/// longer than its limit in characters or not a URI where one is expected
+fn validate_metadata(metadata: &ListingMetadata) -> StdResult<()> {
+    validate_text("Title", &metadata.title, MAX_TITLE_LENGTH)?;
+    if let Some(image) = &metadata.image {
+        validate_uri("Image URI", image)?;
+    }
+    if let Some(external_url) = &metadata.external_url {
+        validate_uri("External URL", external_url)?;
+    }
+    if metadata.attributes.len() > MAX_ATTRIBUTES {
+        return Err(StdError::generic_err("Too many attributes"));
+    }
+    for attribute in &metadata.attributes {
+        validate_text("Attribute trait type", &attribute.trait_type, MAX_TRAIT_TYPE_LENGTH)?;
+        validate_text("Attribute value", &attribute.value, MAX_TRAIT_VALUE_LENGTH)?;
+    }
+    Ok(())
+}
+
+fn validate_text(field: &str, text: &str, max_length: usize) -> StdResult<()> {
+    if text.is_empty() {
+        Err(StdError::generic_err(format!("{} is empty", field)))
+    } else if text.chars().count() > max_length {
+        Err(StdError::generic_err(format!("{} too long", field)))
     } else {
         Ok(())
     }
 }
 
+// a URI needs a scheme, e.g. https: or ipfs:, followed by something
+fn validate_uri(field: &str, uri: &str) -> StdResult<()> {
+    validate_text(field, uri, MAX_URI_LENGTH)?;
+    let valid = match uri.find(':') {
+        Some(colon) => {
+            let scheme = &uri[..colon];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && colon + 1 < uri.len()
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(StdError::generic_err(format!("{} is not a valid URI", field)))
+    }
+}
+
 /// validate_end_height returns an error if the listing ends in the past
-fn validate_end_height(end_height: Option<u64>, env: Env) -> StdResult<()> {
-    if end_height.is_some() && env.block.height >= end_height.unwrap() {
-        Err(StdError::generic_err("Listing cannot end in the past"))
+fn validate_end_height(end: Option<Expiration>, env: Env) -> StdResult<()> {
+    match end {
+        Some(end) if end.is_expired(&env.block) => {
+            Err(StdError::generic_err("Listing cannot end in the past"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// default_end returns the end of a listing that does not set one, a default
+/// duration after its start measured in the start's unit
+fn default_end(start: Option<Expiration>, env: &Env) -> StdResult<Expiration> {
+    let end = match start {
+        Some(Expiration::AtTime(time)) => {
+            time.checked_add(*DEFAULT_END_TIME_SECONDS).map(Expiration::AtTime)
+        }
+        Some(Expiration::AtHeight(height)) => {
+            height.checked_add(*DEFAULT_END_HEIGHT_BLOCKS).map(Expiration::AtHeight)
+        }
+        None => env.block.height.checked_add(*DEFAULT_END_HEIGHT_BLOCKS).map(Expiration::AtHeight),
+    };
+    // only a start near the end of u64 overflows, and that is never within max_start_delay
+    end.ok_or_else(|| StdError::generic_err("Listing starts too far in the future"))
+}
+
+/// validate_durations returns an error if the shortest allowed listing is
+/// longer than the longest, in either unit
+fn validate_durations(min_duration: &Duration, max_duration: &Duration) -> StdResult<()> {
+    if min_duration.blocks > max_duration.blocks || min_duration.seconds > max_duration.seconds {
+        Err(StdError::generic_err("Minimum duration exceeds maximum duration"))
+    } else {
+        Ok(())
+    }
+}
+
+/// validate_schedule returns an error if a listing starts after it ends, is
+/// scheduled too far ahead or runs for less or more than the configured durations
+fn validate_schedule(
+    start: Option<Expiration>,
+    end: Expiration,
+    state: &State,
+    env: &Env,
+) -> StdResult<()> {
+    let now = end.now(&env.block);
+    // a listing opens no earlier than now, so durations are timed from there
+    let opens_at = match start {
+        Some(start) if !start.same_unit(&end) => {
+            return Err(StdError::generic_err(
+                "Listing start and end must use the same unit",
+            ));
+        }
+        Some(start) if start.value() >= end.value() => {
+            return Err(StdError::generic_err("Listing must start before it ends"));
+        }
+        Some(start)
+            if start.value() > now.saturating_add(state.max_start_delay.in_unit_of(&end)) =>
+        {
+            return Err(StdError::generic_err("Listing starts too far in the future"));
+        }
+        Some(start) => start.value().max(now),
+        None => now,
+    };
+
+    let duration = end.value().saturating_sub(opens_at);
+    if duration < state.min_duration.in_unit_of(&end) {
+        Err(StdError::generic_err("Listing duration too short"))
+    } else if duration > state.max_duration.in_unit_of(&end) {
+        Err(StdError::generic_err("Listing duration too long"))
     } else {
         Ok(())
     }
 }
 
 /// create a new listing
-pub fn create_listing<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+#[allow(clippy::too_many_arguments)]
+pub fn create_listing(
+    deps: DepsMut<NftQuery>,
     env: Env,
     info: MessageInfo,
+    kind: ListingKind,
+    denom: String,
+    token_id: String,
     minimum_bid : Uint128,
-    start_height: Option<u64>,
-    end_height: Option<u64>,
+    start: Option<Expiration>,
+    end: Option<Expiration>,
     description: String,
-) -> HandleResult {
+    metadata: Option<ListingMetadata>,
+    reserve_commitment: Option<Binary>,
+) -> StdResult<Response<NftChainMsg>> {
 
-    validate_end_height(end_height, env.clone())?;
+    validate_end_height(end, env.clone())?;
     validate_description(&description)?;
+    if let Some(metadata) = &metadata {
+        validate_metadata(metadata)?;
+    }
+
+    let mut state = CONFIG.load(deps.storage)?;
+    let end = match end {
+        Some(end) => end,
+        None => default_end(start, &env)?,
+    };
+    // a fixed-price listing stays open until bought or delisted, so its end
+    // is never reached and the auction durations do not apply
+    if kind == ListingKind::Auction {
+        validate_schedule(start, end, &state, &env)?;
+    }
 
-    let mut state = config(&mut deps.storage).load()?;
     let listing_count = state.listing_count;
     let listing_id = listing_count + 1;
     state.listing_count = listing_id;
 
-    let sender_address_raw = deps.api.canonical_address(&info.sender)?;
-
-    let sent_nfts = info
-        .sent_nfts
-        .iter()
-        .nth(0)
-        .unwrap();
-
-    let token_id = sent_nfts.id.to_string();
-    let denom = sent_nfts.denom.to_string();
+    assert_nft_owner(deps.as_ref(), &env, &info.sender, &denom, &token_id)?;
+    ESCROWED_NFTS.save(deps.storage, (&denom, &token_id), &listing_id)?;
+    CREATOR_LISTINGS.save(deps.storage, (&info.sender, listing_id), &Empty {})?;
+    let token_uri = query_token_uri(deps.as_ref(), &denom, &token_id)?;
+    let escrow = NftToken::new(&denom, &token_id).transfer_from(
+        &env.contract.address,
+        &info.sender,
+        &env.contract.address,
+    );
+
+    // a listing starting later is announced now and opens for bids at its start
+    let status = match start {
+        Some(start) if !start.is_expired(&env.block) => BidStatus::Scheduled,
+        _ => BidStatus::InProgress,
+    };
 
     let new_listing = Listing {
+        kind,
         token_id,
         denom,
-        creator: sender_address_raw.clone(),
-        status: BidStatus::InProgress,
+        creator: info.sender.clone(),
+        status,
         highest_bid: Uint128::zero(),
-        highest_bidder: sender_address_raw.clone(),
+        highest_bidder: info.sender,
         minimum_bid,
         bidders: vec![],
         bidders_info: vec![],
-        start_height,
-        end_height: end_height.unwrap_or(env.block.height + DEFAULT_END_HEIGHT_BLOCKS),
+        start,
+        end,
         description,
+        metadata,
+        token_uri,
+        nft_claimed: false,
+        proceeds_claimed: false,
+        reserve_met: reserve_commitment.is_none(),
+        reserve_commitment,
     };
-    //
-    let key = state.listing_count.to_string();
-    listing(&mut deps.storage).save(key.as_bytes(), &new_listing)?;
-
-    config(&mut deps.storage).save(&state)?;
-
-    let r = HandleResponse {
-        messages: vec![],
-        attributes : vec![
-            Attribute { key:"action".to_string(), value:"create_listing".to_string(), },
-            // Attribute { key: "creator".to_string(), value: deps.api.human_address(&new_listing.creator)?.to_string(), },
-            Attribute { key: "listing_id".to_string(), value: listing_id.to_string(), },
-            // Attribute { key: "end_height".to_string(), value: new_listing.end_height.to_string(), },
-            // Attribute { key: "start_height".to_string(), value: start_height.unwrap_or(0).to_string(), },
-        ],
-        data: Some(to_binary(&CreateListingResponse { listing_id })?),
-    };
+    LISTINGS.save(deps.storage, listing_id, &new_listing)?;
+    record_status(deps.storage, listing_id, None, &new_listing.status)?;
+    if let (BidStatus::Scheduled, Some(start)) = (&new_listing.status, new_listing.start) {
+        SCHEDULED_LISTINGS.save(deps.storage, listing_id, &start)?;
+    }
+
+    CONFIG.save(deps.storage, &state)?;
+
+    let r = Response::new()
+        .add_attribute("action", "create_listing")
+        .add_message(escrow)
+        // .add_attribute("creator", new_listing.creator.to_string())
+        .add_attribute("listing_id", listing_id.to_string())
+        // .add_attribute("end_height", new_listing.end_height.to_string())
+        // .add_attribute("start_height", start_height.unwrap_or(0).to_string())
+        .set_data(to_binary(&CreateListingResponse { listing_id })?);
 
     Ok(r)
 
 }
 
+/// reserve_hash returns the commitment a seller publishes for a hidden reserve
+pub fn reserve_hash(price: Uint128, salt: &str) -> Binary {
+    let preimage = format!("{}:{}", price, salt);
+    Binary(Sha256::digest(preimage.as_bytes()).to_vec())
+}
+
 /*
  * Ends a listing. Only the creator of a given listing can end that listing.
+ * A listing with a hidden reserve passes only if the revealed reserve matches
+ * the commitment and the highest bid meets it; without a reveal it is rejected.
+ * Settlement only records the outcome and takes the winning bid out of the
+ * bidder's balance; the NFT and the proceeds are pulled afterwards with
+ * ClaimNft and ClaimProceeds.
  */
-pub fn end_listing<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
+pub fn end_listing(
+    deps: DepsMut<NftQuery>,
     env: Env,
     info: MessageInfo,
     listing_id: u64,
-) -> HandleResult {
-    let key = &listing_id.to_string();
-    let mut a_listing = listing(&mut deps.storage).load(key.as_bytes())?;
+    reserve: Option<ReserveReveal>,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = match LISTINGS.may_load(deps.storage, listing_id)? {
+        Some(a_listing) => a_listing,
+        None => return Err(StdError::generic_err("Listing does not exist")),
+    };
+
+    if a_listing.kind != ListingKind::Auction {
+        return Err(StdError::generic_err("Listing is not an auction"));
+    }
 
-    let sender_address_raw = deps.api.canonical_address(&info.sender)?;
-    if a_listing.creator != sender_address_raw {
+    if !matches!(a_listing.status, BidStatus::InProgress | BidStatus::Scheduled) {
+        return Err(StdError::generic_err("Listing is not in progress"));
+    }
+
+    // once the window is over anyone can settle, so bids are never locked
+    // waiting on the creator; before that only the creator can withdraw
+    let expired = a_listing.end.is_expired(&env.block);
+    if !expired && a_listing.creator != info.sender {
         return Err(StdError::generic_err(
             "User is not the creator of the listing.",
         ));
     }
 
-    // if a_listing.status != BidStatus::InProgress {
-    //     return Err(StdError::generic_err("Listing is not in progress"));
-    // }
-    //
-    // if a_listing.start_height.is_some() && a_listing.start_height.unwrap() > env.block.height {
-    //     return Err(StdError::generic_err("Listing period has not started."));
-    // }
-    //
-    // if a_listing.end_height > env.block.height {
-    //     return Err(StdError::generic_err("Listing period has not expired."));
-    // }
+    // bidders are promised the whole window; a listing nobody has bid on,
+    // scheduled or open, can be withdrawn early and its NFT claimed back
+    if !a_listing.bidders.is_empty() && !expired {
+        return Err(StdError::generic_err("Listing period has not expired."));
+    }
+
+    if let Some(commitment) = &a_listing.reserve_commitment {
+        a_listing.reserve_met = match reserve {
+            Some(reserve) => {
+                if &reserve_hash(reserve.price, &reserve.salt) != commitment {
+                    return Err(StdError::generic_err("Reserve does not match the commitment"));
+                }
+                reserve.price <= a_listing.highest_bid
+            }
+            None => false,
+        };
+    }
 
     let mut rejected_reason = "";
     let mut passed = false;
+    let previous_status = a_listing.status.clone();
+    let leader = a_listing.highest_bidder.clone();
 
-    if a_listing.minimum_bid <= a_listing.highest_bid {
-        a_listing.status = BidStatus::Passed;
-
-    } else {
+    if a_listing.bidders.is_empty() {
+        rejected_reason = "No bids placed";
+        a_listing.highest_bidder = a_listing.creator.clone();
+        a_listing.status = BidStatus::Rejected;
+    } else if a_listing.minimum_bid > a_listing.highest_bid {
         rejected_reason = "Bid price not reached minimum";
         a_listing.highest_bidder = a_listing.creator.clone();
         a_listing.status = BidStatus::Rejected;
+    } else if !a_listing.reserve_met {
+        rejected_reason = "Reserve price not met";
+        a_listing.highest_bidder = a_listing.creator.clone();
+        a_listing.status = BidStatus::Rejected;
+    } else {
+        passed = true;
+        a_listing.status = BidStatus::Passed;
     }
 
-    listing(&mut deps.storage).save(key.as_bytes(), &a_listing)?;
-
-    let creator_address = a_listing.creator.clone();
-    let bidder_address = a_listing.highest_bidder.clone();
-    let creator_key = creator_address.as_slice();
-    let bidder_key = bidder_address.as_slice();
-    let token_id = a_listing.token_id;
-    let denom = a_listing.denom;
-    let price = a_listing.highest_bid;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+    record_status(deps.storage, listing_id, Some(&previous_status), &a_listing.status)?;
+    if !passed {
+        LEADING_BIDS.remove(deps.storage, (&leader, listing_id));
+        CREATOR_LISTINGS.remove(deps.storage, (&a_listing.creator, listing_id));
+    }
 
-    let mut creator_token_manager = bank_read(&deps.storage).may_load(creator_key)?.unwrap_or_default();
-    creator_token_manager.token_balance = creator_token_manager.token_balance + price;
-    bank(&mut deps.storage).save(creator_key, &creator_token_manager)?;
+    // the winning bid leaves the bidder's balance and is held for the creator
+    // until ClaimProceeds; a rejected listing moves nothing
+    if passed {
+        let bidder = &a_listing.highest_bidder;
+        let price = a_listing.highest_bid;
 
-    let mut bidder_token_manager = bank_read(&deps.storage).may_load(bidder_key)?.unwrap_or_default();
-    bidder_token_manager.token_balance = Uint128::from(creator_token_manager.token_balance.u128() - price.u128());
-    bank(&mut deps.storage).save(bidder_key, &bidder_token_manager)?;
+        let mut bidder_token_manager = BANK.may_load(deps.storage, bidder)?.unwrap_or_default();
+        bidder_token_manager.token_balance = checked_sub(bidder_token_manager.token_balance, price)?;
+        BANK.save(deps.storage, bidder, &bidder_token_manager)?;
 
+        let mut state = CONFIG.load(deps.storage)?;
+        state.staked_tokens = checked_sub(state.staked_tokens, price)?;
+        CONFIG.save(deps.storage, &state)?;
+        record_sale(deps.storage, coin(price.u128(), &state.denom), 1)?;
+    }
 
     for bidder in &a_listing.bidders {
-        unlock_tokens(deps, bidder, listing_id)?;
+        unlock_tokens(deps.storage, bidder, listing_id)?;
     }
 
-    let contract_address_raw = deps.api.canonical_address(&env.contract.address)?;
-    let bidder_address = a_listing.highest_bidder.clone();
+    let r = Response::new()
+        .add_attribute("action", "end_listing")
+        .add_attribute("listing_id", listing_id.to_string())
+        .add_attribute("rejected_reason", rejected_reason.to_string())
+        .add_attribute("passed", passed.to_string());
+    Ok(r)
+}
+
+/*
+ * Sends the NFT of a settled listing to the winner, or back to the creator if
+ * the listing was rejected. Can be claimed once.
+ */
+pub fn claim_nft(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = match LISTINGS.may_load(deps.storage, listing_id)? {
+        Some(a_listing) => a_listing,
+        None => return Err(StdError::generic_err("Listing does not exist")),
+    };
+
+    if matches!(a_listing.status, BidStatus::InProgress | BidStatus::Scheduled) {
+        return Err(StdError::generic_err("Listing is not settled"));
+    }
+
+    // highest_bidder is reset to the creator when a listing is rejected
+    if a_listing.highest_bidder != info.sender {
+        return Err(StdError::generic_err("User cannot claim the NFT of this listing."));
+    }
+
+    if a_listing.nft_claimed {
+        return Err(StdError::generic_err("NFT already claimed"));
+    }
+    a_listing.nft_claimed = true;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+    LEADING_BIDS.remove(deps.storage, (&info.sender, listing_id));
+
     send_nft(
-        &deps.api,
-        &contract_address_raw,
-        &bidder_address,
-        token_id.to_string(),
-        denom.to_string(),
-        "approve",
+        deps.storage,
+        &env,
+        &info.sender,
+        a_listing.token_id,
+        a_listing.denom,
+        "claim_nft",
+    )
+}
+
+/*
+ * Pays the winning bid of a passed listing out to its creator. Can be claimed once.
+ */
+pub fn claim_proceeds(
+    deps: DepsMut<NftQuery>,
+    _env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = match LISTINGS.may_load(deps.storage, listing_id)? {
+        Some(a_listing) => a_listing,
+        None => return Err(StdError::generic_err("Listing does not exist")),
+    };
+
+    if a_listing.creator != info.sender {
+        return Err(StdError::generic_err(
+            "User is not the creator of the listing.",
+        ));
+    }
+
+    if a_listing.status != BidStatus::Passed {
+        return Err(StdError::generic_err("Listing has no proceeds"));
+    }
+
+    if a_listing.proceeds_claimed {
+        return Err(StdError::generic_err("Proceeds already claimed"));
+    }
+    a_listing.proceeds_claimed = true;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+    CREATOR_LISTINGS.remove(deps.storage, (&info.sender, listing_id));
+
+    let state = CONFIG.load(deps.storage)?;
+    send_tokens(
+        &info.sender,
+        vec![coin(a_listing.highest_bid.u128(), &state.denom)],
+        "claim_proceeds",
+    )
+}
+
+/*
+ * Lists an NFT of the sender for sale at a fixed price, moving it into the contract. It stays
+ * open until it is bought or delisted, so there is no end height to pass.
+ */
+#[allow(clippy::too_many_arguments)]
+pub fn list_fixed_price(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    token_id: String,
+    price: Uint128,
+    description: String,
+    metadata: Option<ListingMetadata>,
+) -> StdResult<Response<NftChainMsg>> {
+    if price.is_zero() {
+        return Err(StdError::generic_err("Price must be positive"));
+    }
+    create_listing(
+        deps,
+        env,
+        info,
+        ListingKind::FixedPrice,
+        denom,
+        token_id,
+        price,
+        None,
+        None,
+        description,
+        metadata,
+        None,
     )
 }
 
+/*
+ * Buys a fixed-price listing. The price is paid out of the buyer's balance,
+ * topped up by any sent funds, and the NFT is sent right away. The seller
+ * pulls the payment with ClaimProceeds. The buyer names the price they saw,
+ * so an UpdatePrice landing first can't charge them more (or less).
+ */
+pub fn buy(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+    price: Uint128,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = load_fixed_price_listing(deps.storage, listing_id)?;
+
+    if a_listing.creator == info.sender {
+        return Err(StdError::generic_err("Creator cannot buy their own listing"));
+    }
+    if price != a_listing.minimum_bid {
+        return Err(StdError::generic_err("Listing price has changed"));
+    }
+
+    escrow_tokens(deps.storage, &info.sender, &info.funds, price)?;
+
+    a_listing.status = BidStatus::Passed;
+    a_listing.highest_bid = price;
+    a_listing.highest_bidder = info.sender.clone();
+    a_listing.nft_claimed = true;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+    record_status(deps.storage, listing_id, Some(&BidStatus::InProgress), &BidStatus::Passed)?;
+    let state = CONFIG.load(deps.storage)?;
+    record_sale(deps.storage, coin(price.u128(), &state.denom), 1)?;
+
+    let r = send_nft(
+        deps.storage,
+        &env,
+        &info.sender,
+        a_listing.token_id,
+        a_listing.denom,
+        "buy",
+    )?;
+    Ok(r
+        .add_attribute("listing_id", listing_id.to_string())
+        .add_attribute("price", price.to_string()))
+}
+
+// changes the asking price of an unsold fixed-price listing
+pub fn update_price(
+    deps: DepsMut<NftQuery>,
+    _env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+    price: Uint128,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = load_fixed_price_listing(deps.storage, listing_id)?;
+
+    if a_listing.creator != info.sender {
+        return Err(StdError::generic_err(
+            "User is not the creator of the listing.",
+        ));
+    }
+    if price.is_zero() {
+        return Err(StdError::generic_err("Price must be positive"));
+    }
+
+    a_listing.minimum_bid = price;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+
+    let r = Response::new()
+        .add_attribute("action", "update_price")
+        .add_attribute("listing_id", listing_id.to_string())
+        .add_attribute("price", price.to_string());
+    Ok(r)
+}
+
+// withdraws an unsold fixed-price listing and returns the NFT to its creator
+pub fn delist(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = load_fixed_price_listing(deps.storage, listing_id)?;
+
+    if a_listing.creator != info.sender {
+        return Err(StdError::generic_err(
+            "User is not the creator of the listing.",
+        ));
+    }
+
+    a_listing.status = BidStatus::Rejected;
+    a_listing.nft_claimed = true;
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+    CREATOR_LISTINGS.remove(deps.storage, (&info.sender, listing_id));
+    record_status(deps.storage, listing_id, Some(&BidStatus::InProgress), &BidStatus::Rejected)?;
+
+    let r = send_nft(
+        deps.storage,
+        &env,
+        &info.sender,
+        a_listing.token_id,
+        a_listing.denom,
+        "delist",
+    )?;
+    Ok(r.add_attribute("listing_id", listing_id.to_string()))
+}
+
+// loads a fixed-price listing that is still for sale
+fn load_fixed_price_listing(
+    storage: &dyn Storage,
+    listing_id: u64,
+) -> StdResult<Listing> {
+    let a_listing = match LISTINGS.may_load(storage, listing_id)? {
+        Some(a_listing) => a_listing,
+        None => return Err(StdError::generic_err("Listing does not exist")),
+    };
+    if a_listing.kind != ListingKind::FixedPrice {
+        return Err(StdError::generic_err("Listing is not for sale at a fixed price"));
+    }
+    if a_listing.status != BidStatus::InProgress {
+        return Err(StdError::generic_err("Listing is not in progress"));
+    }
+    Ok(a_listing)
+}
+
+/*
+ * Edits the description, minimum bid or end of an auction that has no bids
+ * yet, whether scheduled or open. Only the creator can edit a listing.
+ */
+pub fn update_listing(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    listing_id: u64,
+    description: Option<String>,
+    minimum_bid: Option<Uint128>,
+    end: Option<Expiration>,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut a_listing = match LISTINGS.may_load(deps.storage, listing_id)? {
+        Some(a_listing) => a_listing,
+        None => return Err(StdError::generic_err("Listing does not exist")),
+    };
+
+    if a_listing.creator != info.sender {
+        return Err(StdError::generic_err(
+            "User is not the creator of the listing.",
+        ));
+    }
+
+    if a_listing.kind != ListingKind::Auction {
+        return Err(StdError::generic_err("Listing is not an auction"));
+    }
+
+    if !matches!(a_listing.status, BidStatus::InProgress | BidStatus::Scheduled) {
+        return Err(StdError::generic_err("Listing is not in progress"));
+    }
+
+    if !a_listing.bidders.is_empty() {
+        return Err(StdError::generic_err(
+            "Listing cannot be edited after the first bid",
+        ));
+    }
+
+    let mut r = Response::new()
+        .add_attribute("action", "update_listing")
+        .add_attribute("listing_id", listing_id.to_string());
+    if let Some(description) = description {
+        validate_description(&description)?;
+        r = r.add_attribute("description", description.clone());
+        a_listing.description = description;
+    }
+    if let Some(minimum_bid) = minimum_bid {
+        r = r.add_attribute("minimum_bid", minimum_bid.to_string());
+        a_listing.minimum_bid = minimum_bid;
+    }
+    if let Some(end) = end {
+        validate_end_height(Some(end), env.clone())?;
+        let state = CONFIG.load(deps.storage)?;
+        validate_schedule(a_listing.start, end, &state, &env)?;
+        r = r.add_attribute("end", end.to_string());
+        a_listing.end = end;
+    }
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+
+    Ok(r)
+}
+
 // unlock bidder's tokens in a given listing
-fn unlock_tokens<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    bidder: &CanonicalAddr,
+fn unlock_tokens(
+    storage: &mut dyn Storage,
+    bidder: &Addr,
     listing_id: u64,
-) -> HandleResult {
-    let bidder_key = &bidder.as_slice();
-    let mut token_manager = bank_read(&deps.storage).load(bidder_key).unwrap();
+) -> StdResult<()> {
+    let mut token_manager = match BANK.may_load(storage, bidder)? {
+        Some(token_manager) => token_manager,
+        None => return Err(StdError::generic_err("Bidder has no staked tokens")),
+    };
 
     // unlock entails removing the mapped listing_id, retaining the rest
     token_manager.locked_tokens.retain(|(k, _)| k != &listing_id);
-    bank(&mut deps.storage).save(bidder_key, &token_manager)?;
-    Ok(HandleResponse::default())
+    BANK.save(storage, bidder, &token_manager)
 }
 
-// finds the largest locked amount in participated listings.
-fn locked_amount<S: Storage, A: Api, Q: Querier>(
-    bidder: &CanonicalAddr,
-    deps: &mut Extern<S, A, Q>,
-) -> u128 {
-    let bidder_key = &bidder.as_slice();
-    let token_manager = bank_read(&deps.storage).load(bidder_key).unwrap();
+// keeps the per-status listing counts of Stats in step with a status change
+fn record_status(
+    storage: &mut dyn Storage,
+    listing_id: u64,
+    from: Option<&BidStatus>,
+    to: &BidStatus,
+) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    stats.count_status(from, to)?;
+    if from == Some(&BidStatus::Scheduled) {
+        SCHEDULED_LISTINGS.remove(storage, listing_id);
+    }
+    STATS.save(storage, &stats)
+}
+
+// adds `sales` settled NFT sales, paid `amount` in total, to the volume of its denom
+fn record_sale(storage: &mut dyn Storage, amount: Coin, sales: u64) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    stats.sales += sales;
+    match stats.volume.iter_mut().find(|c| c.denom == amount.denom) {
+        Some(volume) => volume.amount = checked_add(volume.amount, amount.amount)?,
+        None => stats.volume.push(amount),
+    }
+    STATS.save(storage, &stats)
+}
+
+// sums the amounts locked by open bids; each bid is backed by its own tokens
+fn locked_amount(
+    storage: &dyn Storage,
+    bidder: &Addr,
+) -> StdResult<Uint128> {
+    let token_manager = BANK.may_load(storage, bidder)?.unwrap_or_default();
+    total_locked(&token_manager)
+}
+
+fn total_locked(token_manager: &TokenManager) -> StdResult<Uint128> {
     token_manager
         .locked_tokens
         .iter()
-        .map(|(_, v)| v.u128())
-        .max()
-        .unwrap_or_default()
+        .try_fold(Uint128::zero(), |total, (_, v)| checked_add(total, *v))
 }
 
-fn has_bidden(bidder: &CanonicalAddr, a_listing: &Listing) -> bool {
+fn has_bidden(bidder: &Addr, a_listing: &Listing) -> bool {
     a_listing.bidders.iter().any(|i| i == bidder)
 }
 
 // stake token and bid for listing
-pub fn bid<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    _env: Env,
+pub fn bid(
+    deps: DepsMut<NftQuery>,
+    env: Env,
     info: MessageInfo,
     listing_id: u64,
     price: Uint128,
-) -> HandleResult {
-    let sender_address_raw = deps.api.canonical_address(&info.sender)?;
-    let listing_key = &listing_id.to_string();
-    let bank_key = sender_address_raw.as_slice();
-    let state = config_read(&deps.storage).load()?;
+    refund_surplus: bool,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut state = CONFIG.load(deps.storage)?;
 
     if listing_id == 0 || state.listing_count < listing_id {
         return Err(StdError::generic_err("Listing does not exist"));
     }
 
-    let mut a_listing = listing(&mut deps.storage).load(listing_key.as_bytes())?;
+    let mut a_listing = LISTINGS.load(deps.storage, listing_id)?;
 
-    if a_listing.status != BidStatus::InProgress {
-        return Err(StdError::generic_err("Listing is not in progress"));
+    if a_listing.kind != ListingKind::Auction {
+        return Err(StdError::generic_err("Listing is not an auction"));
+    }
+
+    match a_listing.status_at(&env.block) {
+        BidStatus::InProgress => {
+            // the first bid after its start moves a scheduled listing over
+            if a_listing.status == BidStatus::Scheduled {
+                record_status(
+                    deps.storage,
+                    listing_id,
+                    Some(&BidStatus::Scheduled),
+                    &BidStatus::InProgress,
+                )?;
+            }
+            a_listing.status = BidStatus::InProgress;
+        }
+        BidStatus::Scheduled => return Err(StdError::generic_err("Listing has not started")),
+        _ => return Err(StdError::generic_err("Listing is not in progress")),
+    }
+
+    if a_listing.end.is_expired(&env.block) {
+        return Err(StdError::generic_err("Listing has ended"));
     }
 
     if price <= a_listing.highest_bid {
         return Err(StdError::generic_err("Set price higher than highest bid"));
     }
 
-    if has_bidden(&sender_address_raw, &a_listing) {
+    if has_bidden(&info.sender, &a_listing) {
         return Err(StdError::generic_err("User has already bidden."));
     }
 
-    let sent_funds = info
-        .sent_funds
+    // foreign denoms are rejected so they are never stuck in the contract
+    assert_only_denom(&info.funds, &state.denom)?;
+
+    // bids without attached coins are funded from the existing balance
+    let sent_amount = info
+        .funds
         .iter()
         .find(|coin| coin.denom.eq(&state.denom))
-        .unwrap();
+        .map(|coin| coin.amount)
+        .unwrap_or_else(Uint128::zero);
+
+    // with refund_surplus, anything sent above the bid price goes straight back
+    let surplus = if refund_surplus && sent_amount > price {
+        checked_sub(sent_amount, price)?
+    } else {
+        Uint128::zero()
+    };
+    let kept_amount = checked_sub(sent_amount, surplus)?;
 
-    let mut token_manager = bank_read(&deps.storage).may_load(bank_key)?.unwrap_or_default();
+    let mut token_manager = BANK.may_load(deps.storage, &info.sender)?.unwrap_or_default();
 
-    if token_manager.token_balance + sent_funds.amount < price {
+    let token_balance = checked_add(token_manager.token_balance, kept_amount)?;
+    // tokens already locked by other open bids cannot back this one too
+    if checked_add(total_locked(&token_manager)?, price)? > token_balance {
         return Err(StdError::generic_err(
             "User does not have enough staked tokens.",
         ));
     }
     // add sent funds to token manager balance
-    token_manager.token_balance =  token_manager.token_balance + sent_funds.amount;
+    token_manager.token_balance = token_balance;
     token_manager.participated_bids.push(listing_id);
     token_manager.locked_tokens.push((listing_id, price));
-    bank(&mut deps.storage).save(bank_key, &token_manager)?;
+    BANK.save(deps.storage, &info.sender, &token_manager)?;
+
+    state.staked_tokens = checked_add(state.staked_tokens, kept_amount)?;
+    CONFIG.save(deps.storage, &state)?;
 
     // mutation for listing state
-    a_listing.bidders.push(sender_address_raw.clone());
-    let bidder_info = Bidder { bidder: sender_address_raw.clone(), price};
+    a_listing.bidders.push(info.sender.clone());
+    let bidder_info = Bidder { bidder: info.sender.clone(), price};
     a_listing.bidders_info.push(bidder_info);
     a_listing.highest_bid = price;
-    a_listing.highest_bidder = sender_address_raw.clone();
-    listing(&mut deps.storage).save(listing_key.as_bytes(), &a_listing)?;
-
-    let attributes = vec![
-        Attribute { key: "action".to_string(), value: "bidden".to_string(), },
-        Attribute { key: "listing_id".to_string(), value:  listing_id.to_string(), },
-    ];
-
-    let r = HandleResponse {
-        messages: vec![],
-        attributes,
-        data: None,
+    LEADING_BIDS.remove(deps.storage, (&a_listing.highest_bidder, listing_id));
+    LEADING_BIDS.save(deps.storage, (&info.sender, listing_id), &Empty {})?;
+    a_listing.highest_bidder = info.sender.clone();
+    LISTINGS.save(deps.storage, listing_id, &a_listing)?;
+
+    if !BIDDERS.has(deps.storage, &info.sender) {
+        BIDDERS.save(deps.storage, &info.sender, &Empty {})?;
+        let mut stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+        stats.unique_bidders += 1;
+        STATS.save(deps.storage, &stats)?;
+    }
+
+    let mut r = Response::new()
+        .add_attribute("action", "bidden")
+        .add_attribute("listing_id", listing_id.to_string());
+
+    if !surplus.is_zero() {
+        r = r
+            .add_attribute("refund", surplus.to_string())
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin(surplus.u128(), &state.denom)],
+            });
+    }
+
+    Ok(r)
+}
+
+/*
+ * Makes an offer on an NFT that does not need to be listed. The offered price
+ * is taken out of the offerer's balance (topped up by any sent funds) and held
+ * until the offer is accepted or cancelled.
+ */
+pub fn make_offer(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    token_id: String,
+    price: Uint128,
+    expires_at_height: Option<u64>,
+) -> StdResult<Response<NftChainMsg>> {
+    if price.is_zero() {
+        return Err(StdError::generic_err("Offer price must be positive"));
+    }
+    if let Some(expires_at_height) = expires_at_height {
+        if env.block.height >= expires_at_height {
+            return Err(StdError::generic_err("Offer cannot expire in the past"));
+        }
+    }
+
+    escrow_tokens(deps.storage, &info.sender, &info.funds, price)?;
+
+    let mut state = CONFIG.load(deps.storage)?;
+    let offer_id = state.offer_count + 1;
+    state.offer_count = offer_id;
+    CONFIG.save(deps.storage, &state)?;
+
+    let new_offer = Offer {
+        offerer: info.sender,
+        denom,
+        token_id,
+        price,
+        expires_at_height,
+    };
+    save_offer(deps.storage, offer_id, &new_offer)?;
+
+    let r = Response::new()
+        .add_attribute("action", "make_offer")
+        .add_attribute("offer_id", offer_id.to_string())
+        .set_data(to_binary(&CreateOfferResponse { offer_id })?);
+    Ok(r)
+}
+
+/*
+ * Cancels an offer and returns its price to the offerer's balance.
+ * Only the offerer can cancel, also after the offer has expired.
+ */
+pub fn cancel_offer(
+    deps: DepsMut<NftQuery>,
+    _env: Env,
+    info: MessageInfo,
+    offer_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let an_offer = match OFFERS.may_load(deps.storage, offer_id)? {
+        Some(an_offer) => an_offer,
+        None => return Err(StdError::generic_err("Offer does not exist")),
+    };
+
+    if an_offer.offerer != info.sender {
+        return Err(StdError::generic_err("User is not the offerer."));
+    }
+    remove_offer(deps.storage, offer_id, &an_offer);
+
+    credit_tokens(deps.storage, &info.sender, an_offer.price)?;
+
+    let r = Response::new()
+        .add_attribute("action", "cancel_offer")
+        .add_attribute("offer_id", offer_id.to_string());
+    Ok(r)
+}
+
+/*
+ * Accepts an offer. The contract moves the sender's NFT straight to the
+ * offerer and credits the offered price to the sender.
+ */
+pub fn accept_offer(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    offer_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let an_offer = match OFFERS.may_load(deps.storage, offer_id)? {
+        Some(an_offer) => an_offer,
+        None => return Err(StdError::generic_err("Offer does not exist")),
     };
+
+    if let Some(expires_at_height) = an_offer.expires_at_height {
+        if env.block.height >= expires_at_height {
+            return Err(StdError::generic_err("Offer has expired"));
+        }
+    }
+
+    assert_nft_owner(deps.as_ref(), &env, &info.sender, &an_offer.denom, &an_offer.token_id)?;
+    remove_offer(deps.storage, offer_id, &an_offer);
+
+    credit_tokens(deps.storage, &info.sender, an_offer.price)?;
+    let state = CONFIG.load(deps.storage)?;
+    record_sale(deps.storage, coin(an_offer.price.u128(), &state.denom), 1)?;
+
+    let transfer = NftToken::new(an_offer.denom, an_offer.token_id).transfer_from(
+        &env.contract.address,
+        &info.sender,
+        &an_offer.offerer,
+    );
+    Ok(Response::new()
+        .add_attribute("action", "accept_offer")
+        .add_attribute("to", an_offer.offerer.to_string())
+        .add_attribute("offer_id", offer_id.to_string())
+        .add_message(transfer))
+}
+
+/*
+ * Makes an offer for any token of an NFT denom. Holders fill it one NFT at a
+ * time until `quantity` tokens are bought; `price` is paid per token and the
+ * full amount is held until filled or cancelled.
+ */
+pub fn make_collection_offer(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    price: Uint128,
+    quantity: u64,
+    expires_at_height: Option<u64>,
+) -> StdResult<Response<NftChainMsg>> {
+    if price.is_zero() {
+        return Err(StdError::generic_err("Offer price must be positive"));
+    }
+    if quantity == 0 {
+        return Err(StdError::generic_err("Offer quantity must be positive"));
+    }
+    if let Some(expires_at_height) = expires_at_height {
+        if env.block.height >= expires_at_height {
+            return Err(StdError::generic_err("Offer cannot expire in the past"));
+        }
+    }
+
+    let total = checked_mul(price, quantity)?;
+    escrow_tokens(deps.storage, &info.sender, &info.funds, total)?;
+
+    let mut state = CONFIG.load(deps.storage)?;
+    let offer_id = state.collection_offer_count + 1;
+    state.collection_offer_count = offer_id;
+    CONFIG.save(deps.storage, &state)?;
+
+    let new_offer = CollectionOffer {
+        offerer: info.sender,
+        denom,
+        price,
+        quantity,
+        expires_at_height,
+    };
+    save_collection_offer(deps.storage, offer_id, &new_offer)?;
+
+    let r = Response::new()
+        .add_attribute("action", "make_collection_offer")
+        .add_attribute("offer_id", offer_id.to_string())
+        .set_data(to_binary(&CreateOfferResponse { offer_id })?);
     Ok(r)
 }
 
-fn send_tokens<A: Api>(
-    api: &A,
-    from_address: &CanonicalAddr,
-    to_address: &CanonicalAddr,
+/*
+ * Cancels a collection offer and returns the price of the unfilled quantity
+ * to the offerer's balance.
+ */
+pub fn cancel_collection_offer(
+    deps: DepsMut<NftQuery>,
+    _env: Env,
+    info: MessageInfo,
+    offer_id: u64,
+) -> StdResult<Response<NftChainMsg>> {
+    let an_offer = match COLLECTION_OFFERS.may_load(deps.storage, offer_id)? {
+        Some(an_offer) => an_offer,
+        None => return Err(StdError::generic_err("Offer does not exist")),
+    };
+
+    if an_offer.offerer != info.sender {
+        return Err(StdError::generic_err("User is not the offerer."));
+    }
+    remove_collection_offer(deps.storage, offer_id, &an_offer);
+
+    let remaining = checked_mul(an_offer.price, an_offer.quantity)?;
+    credit_tokens(deps.storage, &info.sender, remaining)?;
+
+    let r = Response::new()
+        .add_attribute("action", "cancel_collection_offer")
+        .add_attribute("offer_id", offer_id.to_string());
+    Ok(r)
+}
+
+/*
+ * Fills a collection offer with the given tokens of its denom held by the
+ * sender. Each NFT is moved to the offerer and paid for with the per-token price.
+ */
+pub fn fill_collection_offer(
+    deps: DepsMut<NftQuery>,
+    env: Env,
+    info: MessageInfo,
+    offer_id: u64,
+    token_ids: Vec<String>,
+) -> StdResult<Response<NftChainMsg>> {
+    let mut an_offer = match COLLECTION_OFFERS.may_load(deps.storage, offer_id)? {
+        Some(an_offer) => an_offer,
+        None => return Err(StdError::generic_err("Offer does not exist")),
+    };
+
+    if let Some(expires_at_height) = an_offer.expires_at_height {
+        if env.block.height >= expires_at_height {
+            return Err(StdError::generic_err("Offer has expired"));
+        }
+    }
+
+    if token_ids.is_empty() {
+        return Err(StdError::generic_err("No NFT sent to fill the offer"));
+    }
+    let filled = token_ids.len() as u64;
+    if filled > an_offer.quantity {
+        return Err(StdError::generic_err("Sent more NFTs than the offer quantity"));
+    }
+    for (i, token_id) in token_ids.iter().enumerate() {
+        if token_ids[..i].contains(token_id) {
+            return Err(StdError::generic_err("Sent the same NFT twice"));
+        }
+        assert_nft_owner(deps.as_ref(), &env, &info.sender, &an_offer.denom, token_id)?;
+    }
+
+    an_offer.quantity -= filled;
+    if an_offer.quantity == 0 {
+        remove_collection_offer(deps.storage, offer_id, &an_offer);
+    } else {
+        COLLECTION_OFFERS.save(deps.storage, offer_id, &an_offer)?;
+    }
+
+    let paid = checked_mul(an_offer.price, filled)?;
+    credit_tokens(deps.storage, &info.sender, paid)?;
+    let state = CONFIG.load(deps.storage)?;
+    record_sale(deps.storage, coin(paid.u128(), &state.denom), filled)?;
+
+    let mut r = Response::new()
+        .add_attribute("action", "fill_collection_offer")
+        .add_attribute("offer_id", offer_id.to_string())
+        .add_attribute("filled", filled.to_string());
+    for token_id in token_ids {
+        let transfer = NftToken::new(&an_offer.denom, token_id).transfer_from(
+            &env.contract.address,
+            &info.sender,
+            &an_offer.offerer,
+        );
+        r = r.add_message(transfer);
+    }
+    Ok(r)
+}
+
+// stores an offer along with its entries in the offer indexes
+fn save_offer(storage: &mut dyn Storage, offer_id: u64, an_offer: &Offer) -> StdResult<()> {
+    OFFERS.save(storage, offer_id, an_offer)?;
+    OFFERS_BY_NFT.save(storage, (&an_offer.denom, &an_offer.token_id, offer_id), &Empty {})?;
+    OFFERS_BY_OFFERER.save(storage, (&an_offer.offerer, offer_id), &Empty {})
+}
+
+fn remove_offer(storage: &mut dyn Storage, offer_id: u64, an_offer: &Offer) {
+    OFFERS.remove(storage, offer_id);
+    OFFERS_BY_NFT.remove(storage, (&an_offer.denom, &an_offer.token_id, offer_id));
+    OFFERS_BY_OFFERER.remove(storage, (&an_offer.offerer, offer_id));
+}
+
+fn save_collection_offer(
+    storage: &mut dyn Storage,
+    offer_id: u64,
+    an_offer: &CollectionOffer,
+) -> StdResult<()> {
+    COLLECTION_OFFERS.save(storage, offer_id, an_offer)?;
+    COLLECTION_OFFERS_BY_PRICE.save(
+        storage,
+        (&an_offer.denom, u128::MAX - an_offer.price.u128(), offer_id),
+        &Empty {},
+    )?;
+    COLLECTION_OFFERS_BY_OFFERER.save(storage, (&an_offer.offerer, offer_id), &Empty {})
+}
+
+fn remove_collection_offer(storage: &mut dyn Storage, offer_id: u64, an_offer: &CollectionOffer) {
+    COLLECTION_OFFERS.remove(storage, offer_id);
+    COLLECTION_OFFERS_BY_PRICE.remove(
+        storage,
+        (&an_offer.denom, u128::MAX - an_offer.price.u128(), offer_id),
+    );
+    COLLECTION_OFFERS_BY_OFFERER.remove(storage, (&an_offer.offerer, offer_id));
+}
+
+// takes amount out of an address's balance, topped up by the sent funds, to back an offer
+fn escrow_tokens(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    sent_funds: &[Coin],
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut state = CONFIG.load(storage)?;
+
+    assert_only_denom(sent_funds, &state.denom)?;
+    let sent_amount = sent_funds
+        .iter()
+        .find(|coin| coin.denom.eq(&state.denom))
+        .map(|coin| coin.amount)
+        .unwrap_or_else(Uint128::zero);
+
+    let mut token_manager = BANK.may_load(storage, address)?.unwrap_or_default();
+    let token_balance = checked_add(token_manager.token_balance, sent_amount)?;
+    let locked_balance = locked_amount(storage, address)?;
+    // tokens locked by open bids cannot back an offer as well
+    if checked_add(locked_balance, amount)? > token_balance {
+        return Err(StdError::generic_err(
+            "User does not have enough staked tokens.",
+        ));
+    }
+    token_manager.token_balance = checked_sub(token_balance, amount)?;
+    BANK.save(storage, address, &token_manager)?;
+
+    state.staked_tokens = checked_sub(checked_add(state.staked_tokens, sent_amount)?, amount)?;
+    CONFIG.save(storage, &state)
+}
+
+// adds escrowed tokens back to an address's balance
+fn credit_tokens(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut token_manager = BANK.may_load(storage, address)?.unwrap_or_default();
+    token_manager.token_balance = checked_add(token_manager.token_balance, amount)?;
+    BANK.save(storage, address, &token_manager)?;
+
+    let mut state = CONFIG.load(storage)?;
+    state.staked_tokens = checked_add(state.staked_tokens, amount)?;
+    CONFIG.save(storage, &state)
+}
+
+fn send_tokens(
+    to_address: &Addr,
     amount: Vec<Coin>,
     action: &str,
-) -> HandleResult {
-    let from_human = api.human_address(from_address)?;
-    let to_human = api.human_address(to_address)?;
-    let attributes = vec![Attribute { key: "action".to_string(), value: action.to_string(), }, Attribute { key: "to".to_string(), value: to_human.to_string(), },];
-
-    let r = HandleResponse {
-        messages: vec![CosmosMsg::Bank(BankMsg::Send {
-            from_address: from_human,
-            to_address: to_human,
+) -> StdResult<Response<NftChainMsg>> {
+    let r = Response::new()
+        .add_attribute("action", action.to_string())
+        .add_attribute("to", to_address.to_string())
+        .add_message(BankMsg::Send {
+            to_address: to_address.to_string(),
             amount,
-        })],
-        attributes,
-        data: None,
-    };
+        });
     Ok(r)
 }
 
-fn send_nft<A: Api>(
-    api: &A,
-    from_address: &CanonicalAddr,
-    to_address: &CanonicalAddr,
+// moves an NFT out of the contract to `to_address`; once it leaves it is no longer held for a listing
+fn send_nft(
+    storage: &mut dyn Storage,
+    env: &Env,
+    to_address: &Addr,
     token_id: String,
     denom: String,
     action: &str,
-) -> HandleResult {
-    let from_human = api.human_address(from_address)?;
-    let to_human = api.human_address(to_address)?;
-    let attributes = vec![Attribute { key: "action".to_string(), value: action.to_string(), }, Attribute { key: "to".to_string(), value: to_human.to_string(), },];
-
-    let r = HandleResponse {
-        messages: vec![CosmosMsg::Nft(NftMsg::Transfer {
-            sender: from_human,
-            recipient: to_human,
-            id: token_id.to_string(),
-            denom: denom.to_string(),
-        })],
-        attributes,
-        data: None,
-    };
+) -> StdResult<Response<NftChainMsg>> {
+    ESCROWED_NFTS.remove(storage, (&denom, &token_id));
+
+    let r = Response::new()
+        .add_attribute("action", action.to_string())
+        .add_attribute("to", to_address.to_string())
+        .add_message(NftToken::new(denom, token_id).transfer(&env.contract.address, to_address));
     Ok(r)
 }
 
 //クエリ値をバイナリデータとして返す
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    _deps: &Extern<S, A, Q>,
-    _env: Env,
+#[entry_point]
+pub fn query(
+    deps: Deps<NftQuery>,
+    env: Env,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
     match msg {
-        QueryMsg::Config {} => to_binary(&config_read(&_deps.storage).load()?),
-        QueryMsg::TokenStake { address } => token_balance(_deps, address),
-        QueryMsg::Listing { listing_id } => query_listing(_deps, listing_id),
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::TokenStake { address } => token_balance(deps, address),
+        QueryMsg::Listing { listing_id } => query_listing(deps, env, listing_id),
+        QueryMsg::Listings {
+            kind,
+            start_after,
+            limit,
+        } => query_listings(deps, env, kind, start_after, limit),
+        QueryMsg::Account { address } => query_account(deps, env, address),
+        QueryMsg::OffersByNft {
+            denom,
+            token_id,
+            start_after,
+            limit,
+        } => {
+            let offer_ids = OFFERS_BY_NFT.prefix((&denom, &token_id)).keys(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            );
+            query_offers(deps, offer_ids, limit)
+        }
+        QueryMsg::OffersByOfferer {
+            offerer,
+            start_after,
+            limit,
+        } => {
+            let offerer = deps.api.addr_validate(&offerer)?;
+            let offer_ids = OFFERS_BY_OFFERER.prefix(&offerer).keys(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            );
+            query_offers(deps, offer_ids, limit)
+        }
+        QueryMsg::CollectionOffers {
+            denom,
+            start_after,
+            limit,
+        } => query_collection_offers(deps, denom, start_after, limit),
+        QueryMsg::CollectionOffersByOfferer {
+            offerer,
+            start_after,
+            limit,
+        } => query_collection_offers_by_offerer(deps, offerer, start_after, limit),
+        QueryMsg::Stats {} => query_stats(deps, env),
     }
 }
 
-fn query_listing<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
+fn query_listing(
+    deps: Deps<NftQuery>,
+    env: Env,
     listing_id: u64,
 ) -> StdResult<Binary> {
-    let key = &listing_id.to_string();
 //listing_readはstateにて定義、バイナリをオブジェクト化
-    let listing = match listing_read(&deps.storage).may_load(key.as_bytes())? {
+    let listing = match LISTINGS.may_load(deps.storage, listing_id)? {
 //型マッチしていれば返す
-        Some(listing) => Some(listing),
+        Some(listing) => listing,
         None => return Err(StdError::generic_err("Listing does not exist")),
-    }
-    .unwrap();
+    };
 //listingオブジェクトの情報とメタデータからオブジェクト生成
-    let resp = ListingResponse {
+    let resp = listing_response(&env, listing_id, listing)?;
+//バイナリで返す
+    to_binary(&resp)
+}
+
+// all listings, optionally only those of one kind, in the order they were created
+fn query_listings(
+    deps: Deps<NftQuery>,
+    env: Env,
+    kind: Option<ListingKind>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let mut listings = vec![];
+    for item in LISTINGS.range(deps.storage, start, None, Order::Ascending) {
+        if listings.len() == limit {
+            break;
+        }
+        let (listing_id, a_listing) = item?;
+        if kind.is_none() || kind.as_ref() == Some(&a_listing.kind) {
+            listings.push(listing_response(&env, listing_id, a_listing)?);
+        }
+    }
+
+    to_binary(&ListingsResponse { listings })
+}
+
+fn listing_response(
+    env: &Env,
+    listing_id: u64,
+    listing: Listing,
+) -> StdResult<ListingResponse> {
+    let status = listing.status_at(&env.block);
+    Ok(ListingResponse {
+        listing_id,
+        kind: listing.kind,
         token_id: listing.token_id,
         denom: listing.denom,
-        creator: deps.api.human_address(&listing.creator).unwrap(),
-        status: listing.status,
+        creator: listing.creator,
+        status,
         highest_bid: listing.highest_bid,
-        highest_bidder: deps.api.human_address(&listing.highest_bidder).unwrap(),
-        end_height: Some(listing.end_height),
-        start_height: listing.start_height,
+        highest_bidder: listing.highest_bidder,
+        minimum_bid: listing.minimum_bid,
+        end: listing.end,
+        start: listing.start,
         description: listing.description,
+        metadata: listing.metadata,
+        token_uri: listing.token_uri,
+        nft_claimed: listing.nft_claimed,
+        proceeds_claimed: listing.proceeds_claimed,
+        reserve_met: listing.reserve_met,
+    })
+}
+
+fn token_balance(
+    deps: Deps<NftQuery>,
+    address: String,
+) -> StdResult<Binary> {
+    let address = deps.api.addr_validate(&address)?;
+
+    let token_manager = BANK.may_load(deps.storage, &address)?.unwrap_or_default();
+
+    let resp = TokenStakeResponse {
+        token_balance: token_manager.token_balance,
     };
-//バイナリで返す
+
     to_binary(&resp)
 }
 
-fn token_balance<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: HumanAddr,
+// balances of an address together with the payouts it has not pulled yet.
+// A listing counts as pending once its end height has passed with a bid at or
+// above the minimum, and stays pending after settlement until it is claimed.
+fn query_account(
+    deps: Deps<NftQuery>,
+    env: Env,
+    address: String,
 ) -> StdResult<Binary> {
-    let key = deps.api.canonical_address(&address).unwrap();
+    let address = deps.api.addr_validate(&address)?;
+
+    let token_manager = BANK.may_load(deps.storage, &address)?.unwrap_or_default();
+    let locked_balance = total_locked(&token_manager)?;
+
+    // offers hold their price outside token_balance until cancelled or taken
+    let mut held_balance = Uint128::zero();
+    for offer_id in OFFERS_BY_OFFERER
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+    {
+        let an_offer = OFFERS.load(deps.storage, offer_id?)?;
+        held_balance = checked_add(held_balance, an_offer.price)?;
+    }
+    for offer_id in COLLECTION_OFFERS_BY_OFFERER
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+    {
+        let an_offer = COLLECTION_OFFERS.load(deps.storage, offer_id?)?;
+        held_balance = checked_add(held_balance, checked_mul(an_offer.price, an_offer.quantity)?)?;
+    }
 
-    let token_manager = bank_read(&deps.storage)
-        .may_load(key.as_slice())?
-        .unwrap_or_default();
+    // only the listings indexed under the address are looked at
+    let mut pending_proceeds = vec![];
+    for listing_id in CREATOR_LISTINGS
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+    {
+        let listing_id = listing_id?;
+        let a_listing = LISTINGS.load(deps.storage, listing_id)?;
+        if unclaimed_settlement(&a_listing, &env).0 {
+            pending_proceeds.push((listing_id, a_listing.highest_bid));
+        }
+    }
+    let mut unclaimed_listings = vec![];
+    for listing_id in LEADING_BIDS
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+    {
+        let listing_id = listing_id?;
+        let a_listing = LISTINGS.load(deps.storage, listing_id)?;
+        if unclaimed_settlement(&a_listing, &env).1 {
+            unclaimed_listings.push(listing_id);
+        }
+    }
 
-    let resp = TokenStakeResponse {
+    let resp = AccountResponse {
         token_balance: token_manager.token_balance,
+        available_balance: checked_sub(token_manager.token_balance, locked_balance)?,
+        locked_tokens: token_manager.locked_tokens,
+        held_balance,
+        pending_proceeds,
+        unclaimed_listings,
     };
 
     to_binary(&resp)
 }
 
-pub fn get_nft<S: Storage, A: Api, Q: Querier>(
-    _deps: &mut Extern<S, A, Q>,
+// whether the proceeds and the NFT of a listing are still to be claimed, counting an ended
+// auction that would pass but has not been closed yet
+fn unclaimed_settlement(a_listing: &Listing, env: &Env) -> (bool, bool) {
+    match a_listing.status {
+        BidStatus::Passed => (!a_listing.proceeds_claimed, !a_listing.nft_claimed),
+        BidStatus::InProgress | BidStatus::Scheduled => {
+            // a hidden reserve is unknown until the seller reveals it
+            let won = a_listing.end.is_expired(&env.block)
+                && a_listing.reserve_commitment.is_none()
+                && !a_listing.bidders.is_empty()
+                && a_listing.highest_bid >= a_listing.minimum_bid;
+            (won, won)
+        }
+        _ => (false, false),
+    }
+}
+
+// open offers matching the filter, in the order they were made
+// answers a page of the offer ids an index yields
+fn query_offers(
+    deps: Deps<NftQuery>,
+    offer_ids: impl Iterator<Item = StdResult<u64>>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut offers = vec![];
+    for offer_id in offer_ids.take(limit) {
+        let offer_id = offer_id?;
+        let an_offer = OFFERS.load(deps.storage, offer_id)?;
+        offers.push(OfferResponse {
+            offer_id,
+            offerer: an_offer.offerer,
+            denom: an_offer.denom,
+            token_id: an_offer.token_id,
+            price: an_offer.price,
+            expires_at_height: an_offer.expires_at_height,
+        });
+    }
+
+    to_binary(&OffersResponse { offers })
+}
+
+// running totals, with every status listed even when no listing has it
+fn query_stats(deps: Deps<NftQuery>, env: Env) -> StdResult<Binary> {
+    let state = CONFIG.load(deps.storage)?;
+    let mut stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+
+    // a scheduled listing is stored as such until its first bid or close,
+    // count the ones past their start as in progress, as status_at does
+    for item in SCHEDULED_LISTINGS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, start) = item?;
+        if start.is_expired(&env.block) {
+            stats.count_status(Some(&BidStatus::Scheduled), &BidStatus::InProgress)?;
+        }
+    }
+
+    let listings_by_status = [
+        BidStatus::Scheduled,
+        BidStatus::InProgress,
+        BidStatus::Tally,
+        BidStatus::Passed,
+        BidStatus::Rejected,
+    ]
+    .iter()
+    .map(|status| (status.clone(), stats.status_count(status)))
+    .collect();
+
+    to_binary(&StatsResponse {
+        listing_count: state.listing_count,
+        listings_by_status,
+        volume: stats.volume,
+        sales: stats.sales,
+        staked_tokens: state.staked_tokens,
+        unique_bidders: stats.unique_bidders,
+    })
+}
+
+// open collection offers on a denom, best price first
+fn query_collection_offers(
+    deps: Deps<NftQuery>,
+    denom: String,
+    start_after: Option<(Uint128, u64)>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after
+        .map(|(price, offer_id)| Bound::exclusive((u128::MAX - price.u128(), offer_id)));
+    let mut offers = vec![];
+    for item in COLLECTION_OFFERS_BY_PRICE
+        .sub_prefix(&denom)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+    {
+        let (_, offer_id) = item?;
+        let an_offer = COLLECTION_OFFERS.load(deps.storage, offer_id)?;
+        offers.push(collection_offer_response(offer_id, an_offer));
+    }
+
+    to_binary(&CollectionOffersResponse { offers })
+}
+
+fn query_collection_offers_by_offerer(
+    deps: Deps<NftQuery>,
+    offerer: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    let offerer = deps.api.addr_validate(&offerer)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut offers = vec![];
+    for offer_id in COLLECTION_OFFERS_BY_OFFERER
+        .prefix(&offerer)
+        .keys(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+    {
+        let offer_id = offer_id?;
+        let an_offer = COLLECTION_OFFERS.load(deps.storage, offer_id)?;
+        offers.push(collection_offer_response(offer_id, an_offer));
+    }
+
+    to_binary(&CollectionOffersResponse { offers })
+}
+
+fn collection_offer_response(offer_id: u64, an_offer: CollectionOffer) -> CollectionOfferResponse {
+    CollectionOfferResponse {
+        offer_id,
+        offerer: an_offer.offerer,
+        denom: an_offer.denom,
+        price: an_offer.price,
+        quantity: an_offer.quantity,
+        expires_at_height: an_offer.expires_at_height,
+    }
+}
+
+fn query_nft_owner(
+    deps: Deps<NftQuery>,
+    denom: &str,
+    id: &str,
+) -> StdResult<String> {
+    let request = QueryRequest::Custom(NftQuery::Owner {
+        denom: denom.to_string(),
+        id: id.to_string(),
+    });
+    let res: OwnerResponse = deps.querier.query(&request)?;
+    Ok(res.owner)
+}
+
+// stock MessageInfo carries no NFTs, so the contract pulls them with an approval:
+// the NFT module must report the sender as the owner and the contract as approved
+// to transfer the token, and a token already held for a listing cannot be used again
+fn assert_nft_owner(
+    deps: Deps<NftQuery>,
+    env: &Env,
+    sender: &Addr,
+    denom: &str,
+    id: &str,
+) -> StdResult<()> {
+    let owner = query_nft_owner(deps, denom, id)?;
+    if owner != *sender {
+        return Err(StdError::generic_err("NFT is not owned by the sender"));
+    }
+    let escrowed = ESCROWED_NFTS.may_load(deps.storage, (denom, id))?;
+    if escrowed.is_some() {
+        return Err(StdError::generic_err("NFT is already in escrow"));
+    }
+    let request = QueryRequest::Custom(NftQuery::Approved {
+        denom: denom.to_string(),
+        id: id.to_string(),
+        operator: env.contract.address.to_string(),
+    });
+    let res: ApprovedResponse = deps.querier.query(&request)?;
+    if !res.approved {
+        return Err(StdError::generic_err("Contract is not approved to transfer the NFT"));
+    }
+    Ok(())
+}
+
+fn query_token_uri(
+    deps: Deps<NftQuery>,
+    denom: &str,
+    id: &str,
+) -> StdResult<Option<String>> {
+    let request = QueryRequest::Custom(NftQuery::TokenUri {
+        denom: denom.to_string(),
+        id: id.to_string(),
+    });
+    let res: TokenUriResponse = deps.querier.query(&request)?;
+    Ok(res.token_uri)
+}
+
+pub fn get_nft(
+    _deps: DepsMut<NftQuery>,
     env: Env,
     info: MessageInfo,
     denom: String,
     id: String,
-) -> HandleResult {
+) -> StdResult<Response<NftChainMsg>> {
     let sender_address = info.sender;
     let contract_address = env.contract.address;
     let denom = &denom.to_string();
     let id = &id.to_string();
 
-    let attributes = vec![Attribute { key: "id".to_string(), value: id.to_string(), }, Attribute { key: "to".to_string(), value: sender_address.to_string(), },];
-
-    let r = HandleResponse {
-        messages: vec![CosmosMsg::Nft(NftMsg::Transfer {
-            sender: contract_address,
-            recipient: sender_address,
-            denom: denom.to_string(),
-            id: id.to_string(),
-        })],
-        attributes,
-        data: None,
-    };
+    let r = Response::new()
+        .add_attribute("id", id.to_string())
+        .add_attribute("to", sender_address.to_string())
+        .add_message(NftToken::new(denom, id).transfer(contract_address, sender_address));
     Ok(r)
 }
@@ -1,145 +1,321 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 
-use crate::addresses::HumanAddr;
-use crate::coins::Coin;
-use crate::encoding::Binary;
-use crate::types::Empty;
+use cosmwasm_std::{CosmosMsg, CustomMsg, CustomQuery};
 
+/// messages handled by the chain's NFT module, dispatched inside NftChainMsg
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-// See https://github.com/serde-rs/serde/issues/1296 why we cannot add De-Serialize trait bounds to T
-pub enum CosmosMsg<T = Empty>
-    where
-        T: Clone + fmt::Debug + PartialEq + JsonSchema,
-{
-    Bank(BankMsg),
-    // by default we use RawMsg, but a contract can override that
-    // to call into more app-specific code (whatever they define)
-    Custom(T),
-    Staking(StakingMsg),
-    Wasm(WasmMsg),
-    Nft(NftMsg),
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum BankMsg {
-    // this moves tokens in the underlying sdk
-    Send {
-        from_address: HumanAddr,
-        to_address: HumanAddr,
-        amount: Vec<Coin>,
+pub enum NftMsg {
+    /// moves token `id` of collection `denom` from `sender` to `recipient`
+    Transfer {
+        sender: String,
+        recipient: String,
+        denom: String,
+        id: String,
     },
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum StakingMsg {
-    Delegate {
-        // delegator is automatically set to address of the calling contract
-        validator: HumanAddr,
-        amount: Coin,
+    /// lets `operator` transfer token `id`, which must be owned by `sender`,
+    /// until the token next changes hands
+    Approve {
+        sender: String,
+        operator: String,
+        denom: String,
+        id: String,
     },
-    Undelegate {
-        // delegator is automatically set to address of the calling contract
-        validator: HumanAddr,
-        amount: Coin,
+    /// moves token `id` from `owner` to `recipient`; `sender` must be the
+    /// operator `owner` approved for it
+    TransferFrom {
+        sender: String,
+        owner: String,
+        recipient: String,
+        denom: String,
+        id: String,
+    },
+    /// creates token `id` in collection `denom`, owned by `recipient`
+    Mint {
+        sender: String,
+        recipient: String,
+        denom: String,
+        id: String,
+        token_uri: Option<String>,
     },
-    Withdraw {
-        // delegator is automatically set to address of the calling contract
-        validator: HumanAddr,
-        /// this is the "withdraw address", the one that should receive the rewards
-        /// if None, then use delegator address
-        recipient: Option<HumanAddr>,
+    /// destroys token `id`, which must be owned by `sender`
+    Burn {
+        sender: String,
+        denom: String,
+        id: String,
     },
-    Redelegate {
-        // delegator is automatically set to address of the calling contract
-        src_validator: HumanAddr,
-        dst_validator: HumanAddr,
-        amount: Coin,
+    /// replaces the token URI of token `id`, which must be owned by `sender`
+    EditMetadata {
+        sender: String,
+        denom: String,
+        id: String,
+        token_uri: Option<String>,
     },
 }
 
+/// the custom message type of the contract, sent as `CosmosMsg::Custom`.
+/// The chain routes it by module, so an NFT transfer keeps the
+/// `{"nft":{"transfer":{..}}}` shape the chain already handles
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum WasmMsg {
-    /// this dispatches a call to another contract at a known address (with known ABI)
-    Execute {
-        contract_addr: HumanAddr,
-        /// msg is the json-encoded HandleMsg struct (as raw Binary)
-        msg: Binary,
-        send: Vec<Coin>,
-    },
-    /// this instantiates a new contracts from previously uploaded wasm code
-    Instantiate {
-        code_id: u64,
-        /// msg is the json-encoded InitMsg struct (as raw Binary)
-        msg: Binary,
-        send: Vec<Coin>,
-        /// optional human-readbale label for the contract
-        label: Option<String>,
-    },
+pub enum NftChainMsg {
+    Nft(NftMsg),
 }
 
+impl CustomMsg for NftChainMsg {}
+
+impl From<NftMsg> for NftChainMsg {
+    fn from(msg: NftMsg) -> Self {
+        NftChainMsg::Nft(msg)
+    }
+}
+
+impl From<NftMsg> for CosmosMsg<NftChainMsg> {
+    fn from(msg: NftMsg) -> Self {
+        CosmosMsg::Custom(NftChainMsg::Nft(msg))
+    }
+}
+
+/// a single token of an NFT collection, used to build NftMsg values
+/// without mixing up the order of the string fields
+#[derive(Clone, Debug, PartialEq)]
+pub struct NftToken {
+    pub denom: String,
+    pub id: String,
+}
+
+impl NftToken {
+    pub fn new(denom: impl Into<String>, id: impl Into<String>) -> Self {
+        NftToken {
+            denom: denom.into(),
+            id: id.into(),
+        }
+    }
+
+    pub fn transfer(&self, sender: impl Into<String>, recipient: impl Into<String>) -> NftMsg {
+        NftMsg::Transfer {
+            sender: sender.into(),
+            recipient: recipient.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+        }
+    }
+
+    pub fn approve(&self, sender: impl Into<String>, operator: impl Into<String>) -> NftMsg {
+        NftMsg::Approve {
+            sender: sender.into(),
+            operator: operator.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+        }
+    }
+
+    pub fn transfer_from(
+        &self,
+        sender: impl Into<String>,
+        owner: impl Into<String>,
+        recipient: impl Into<String>,
+    ) -> NftMsg {
+        NftMsg::TransferFrom {
+            sender: sender.into(),
+            owner: owner.into(),
+            recipient: recipient.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+        }
+    }
+
+    pub fn mint(
+        &self,
+        sender: impl Into<String>,
+        recipient: impl Into<String>,
+        token_uri: Option<String>,
+    ) -> NftMsg {
+        NftMsg::Mint {
+            sender: sender.into(),
+            recipient: recipient.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+            token_uri,
+        }
+    }
+
+    pub fn burn(&self, sender: impl Into<String>) -> NftMsg {
+        NftMsg::Burn {
+            sender: sender.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+        }
+    }
 
+    pub fn edit_metadata(&self, sender: impl Into<String>, token_uri: Option<String>) -> NftMsg {
+        NftMsg::EditMetadata {
+            sender: sender.into(),
+            denom: self.denom.clone(),
+            id: self.id.clone(),
+            token_uri,
+        }
+    }
+}
+
+/// queries answered by the chain's NFT module, sent as `QueryRequest::Custom`
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum NftMsg {
-    // this moves tokens in the underlying sdk
-    Tranfer {
-        sender: HumanAddr,
-        recipient: HumanAddr,
+pub enum NftQuery {
+    /// returns OwnerResponse
+    Owner { denom: String, id: String },
+    /// returns TokenUriResponse
+    TokenUri { denom: String, id: String },
+    /// returns ApprovedResponse, whether `operator` may transfer token `id`
+    Approved {
         denom: String,
         id: String,
+        operator: String,
     },
 }
 
+impl CustomQuery for NftQuery {}
 
-impl<T: Clone + fmt::Debug + PartialEq + JsonSchema> From<BankMsg> for CosmosMsg<T> {
-    fn from(msg: BankMsg) -> Self {
-        CosmosMsg::Bank(msg)
-    }
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerResponse {
+    pub owner: String,
 }
 
-#[cfg(feature = "staking")]
-impl<T: Clone + fmt::Debug + PartialEq + JsonSchema> From<StakingMsg> for CosmosMsg<T> {
-    fn from(msg: StakingMsg) -> Self {
-        CosmosMsg::Staking(msg)
-    }
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenUriResponse {
+    pub token_uri: Option<String>,
 }
 
-impl<T: Clone + fmt::Debug + PartialEq + JsonSchema> From<WasmMsg> for CosmosMsg<T> {
-    fn from(msg: WasmMsg) -> Self {
-        CosmosMsg::Wasm(msg)
-    }
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ApprovedResponse {
+    pub approved: bool,
 }
 
-impl<T: Clone + fmt::Debug + PartialEq + JsonSchema> From<NftMsg> for CosmosMsg<T> {
-    fn from(msg: NftMsg) -> Self {
-        CosmosMsg::Nft(msg)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::{from_slice, to_vec};
+    use schemars::schema_for;
+
+    fn token() -> NftToken {
+        NftToken::new("kitties", "kitty-1")
+    }
+
+    fn all_msgs() -> Vec<NftMsg> {
+        vec![
+            token().transfer("seller", "buyer"),
+            token().approve("seller", "auction"),
+            token().transfer_from("auction", "seller", "buyer"),
+            token().mint("minter", "owner", Some("ipfs://kitty-1".to_string())),
+            token().burn("owner"),
+            token().edit_metadata("owner", None),
+        ]
+    }
+
+    #[test]
+    fn builder_fills_token_fields() {
+        assert_eq!(
+            token().transfer("seller", "buyer"),
+            NftMsg::Transfer {
+                sender: String::from("seller"),
+                recipient: String::from("buyer"),
+                denom: "kitties".to_string(),
+                id: "kitty-1".to_string(),
+            }
+        );
+        assert_eq!(
+            token().transfer_from("auction", "seller", "buyer"),
+            NftMsg::TransferFrom {
+                sender: String::from("auction"),
+                owner: String::from("seller"),
+                recipient: String::from("buyer"),
+                denom: "kitties".to_string(),
+                id: "kitty-1".to_string(),
+            }
+        );
+        assert_eq!(
+            token().burn("owner"),
+            NftMsg::Burn {
+                sender: String::from("owner"),
+                denom: "kitties".to_string(),
+                id: "kitty-1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for msg in all_msgs() {
+            let bin = to_vec(&msg).unwrap();
+            let parsed: NftMsg = from_slice(&bin).unwrap();
+            assert_eq!(msg, parsed);
+        }
+
+        let json = br#"{"transfer":{"sender":"seller","recipient":"buyer","denom":"kitties","id":"kitty-1"}}"#;
+        let parsed: NftMsg = from_slice(json).unwrap();
+        assert_eq!(parsed, token().transfer("seller", "buyer"));
+
+        let json = to_vec(&token().edit_metadata("owner", None)).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"edit_metadata":{"sender":"owner","denom":"kitties","id":"kitty-1","token_uri":null}}"#
+        );
+    }
+
+    #[test]
+    fn query_serde_round_trip() {
+        let query = NftQuery::TokenUri {
+            denom: "kitties".to_string(),
+            id: "kitty-1".to_string(),
+        };
+        let bin = to_vec(&query).unwrap();
+        assert_eq!(
+            String::from_utf8(bin.clone()).unwrap(),
+            r#"{"token_uri":{"denom":"kitties","id":"kitty-1"}}"#
+        );
+        assert_eq!(from_slice::<NftQuery>(&bin).unwrap(), query);
+
+        let json = br#"{"approved":{"denom":"kitties","id":"kitty-1","operator":"auction"}}"#;
+        assert_eq!(
+            from_slice::<NftQuery>(json).unwrap(),
+            NftQuery::Approved {
+                denom: "kitties".to_string(),
+                id: "kitty-1".to_string(),
+                operator: "auction".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn converts_into_custom_cosmos_msg() {
+        for msg in all_msgs() {
+            let cosmos: CosmosMsg<NftChainMsg> = msg.clone().into();
+            match cosmos {
+                CosmosMsg::Custom(NftChainMsg::Nft(inner)) => assert_eq!(inner, msg),
+                _ => panic!("must encode in Custom variant"),
+            }
+        }
+
+        let json = to_vec(&NftChainMsg::from(token().transfer("seller", "buyer"))).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"nft":{"transfer":{"sender":"seller","recipient":"buyer","denom":"kitties","id":"kitty-1"}}}"#
+        );
+    }
+
+    #[test]
+    fn schema_lists_every_variant() {
+        let schema = serde_json::to_string(&schema_for!(NftMsg)).unwrap();
+        for variant in &[
+            "transfer",
+            "approve",
+            "transfer_from",
+            "mint",
+            "burn",
+            "edit_metadata",
+        ] {
+            assert!(schema.contains(&format!("\"{}\"", variant)));
+        }
     }
 }
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//     use crate::coins;
-//
-//     #[test]
-//     fn from_bank_msg_works() {
-//         let from_address = HumanAddr::from("me");
-//         let to_address = HumanAddr::from("you");
-//         let amount = coins(1015, "earth");
-//         let bank = BankMsg::Send {
-//             from_address,
-//             to_address,
-//             amount,
-//         };
-//         let msg: CosmosMsg = bank.clone().into();
-//         match msg {
-//             CosmosMsg::Bank(msg) => assert_eq!(bank, msg),
-//             _ => panic!("must encode in Bank variant"),
-//         }
-//     }
-// }
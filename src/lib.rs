@@ -1,10 +1,14 @@
 pub mod coin_helpers;
 pub mod contract;
+pub mod cosmos_msg;
 pub mod msg;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod schema;
 pub mod state;
 
+#[cfg(test)]
+mod multitest;
+#[cfg(test)]
+mod test_utils;
 #[cfg(test)]
 mod tests;
-
-#[cfg(target_arch = "wasm32")]
-cosmwasm_std::create_entry_points!(contract);
@@ -1,31 +1,114 @@
-use crate::state::BidStatus;
-use cosmwasm_std::{HumanAddr, Uint128};
+use crate::state::{BidStatus, Duration, Expiration, ListingKind, ListingMetadata};
+use cosmwasm_std::{Addr, Binary, Coin, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InitMsg {
+pub struct InstantiateMsg {
     pub denom: String,
+    /// listing duration limits, an hour to 30 days by default
+    pub min_duration: Option<Duration>,
+    pub max_duration: Option<Duration>,
+    /// how far ahead a listing may start, 30 days by default
+    pub max_start_delay: Option<Duration>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum HandleMsg {
+pub enum ExecuteMsg {
+    Deposit {},
     Bid {
         listing_id: u64,
         price: Uint128,
+        /// send back any attached amount above `price` instead of keeping it as balance
+        refund_surplus: Option<bool>,
     },
     WithdrawTokens {
         amount: Option<Uint128>,
     },
+    /// the contract takes the NFT from the sender, who must own it and have approved the contract
     List {
+        denom: String,
+        token_id: String,
         minimum_bid : Uint128,
-        start_height: Option<u64>,
-        end_height: Option<u64>,
+        start: Option<Expiration>,
+        /// defaults to a week after `start`, or after now, in the same unit
+        end: Option<Expiration>,
         description: String,
+        metadata: Option<ListingMetadata>,
+        /// sha256 of "<reserve>:<salt>", keeping the reserve hidden until settlement
+        reserve_commitment: Option<Binary>,
     },
+    /// like List, the contract takes the sender's NFT; the first buyer paying `price` gets it
+    ListFixedPrice {
+        denom: String,
+        token_id: String,
+        price: Uint128,
+        description: String,
+        metadata: Option<ListingMetadata>,
+    },
+    /// pays a fixed-price listing from the balance topped up by the sent funds;
+    /// fails unless `price` is still the listing's price
+    Buy {
+        listing_id: u64,
+        price: Uint128,
+    },
+    UpdatePrice {
+        listing_id: u64,
+        price: Uint128,
+    },
+    /// withdraws an unsold fixed-price listing and returns the NFT
+    Delist {
+        listing_id: u64,
+    },
+    /// edits an auction before its first bid; fields left out are kept
+    UpdateListing {
+        listing_id: u64,
+        description: Option<String>,
+        minimum_bid: Option<Uint128>,
+        end: Option<Expiration>,
+    },
+    /// settles an auction once its end has passed, which anyone can do, or
+    /// lets the creator withdraw it early if nobody has bid; a listing with a
+    /// reserve only passes if the reserve is revealed here
     CloseBid {
         listing_id: u64,
+        reserve: Option<ReserveReveal>,
+    },
+    ClaimNft {
+        listing_id: u64,
+    },
+    ClaimProceeds {
+        listing_id: u64,
+    },
+    MakeOffer {
+        denom: String,
+        token_id: String,
+        price: Uint128,
+        expires_at_height: Option<u64>,
+    },
+    CancelOffer {
+        offer_id: u64,
+    },
+    /// sent by the NFT owner, who has approved the contract; the contract moves the NFT to the offerer
+    AcceptOffer {
+        offer_id: u64,
+    },
+    /// `price` is per token; price * quantity is held until filled or cancelled
+    MakeCollectionOffer {
+        denom: String,
+        price: Uint128,
+        quantity: u64,
+        expires_at_height: Option<u64>,
+    },
+    CancelCollectionOffer {
+        offer_id: u64,
+    },
+    /// sent by the owner of the `token_ids` of the offer's denom, who has approved the contract for each;
+    /// the contract moves them to the offerer
+    FillCollectionOffer {
+        offer_id: u64,
+        token_ids: Vec<String>,
     },
     // GetNft {
     //     denom: String,
@@ -33,25 +116,79 @@ pub enum HandleMsg {
     // },
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReserveReveal {
+    pub price: Uint128,
+    pub salt: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
-    TokenStake { address: HumanAddr },
+    TokenStake { address: String },
     Listing { listing_id: u64 },
+    /// listings in id order, `limit` at a time (default 10, at most 30) after `start_after`
+    Listings {
+        kind: Option<ListingKind>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    Account { address: String },
+    /// offers in id order, `limit` at a time (default 10, at most 30) after `start_after`
+    OffersByNft {
+        denom: String,
+        token_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// paged like OffersByNft
+    OffersByOfferer {
+        offerer: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// highest price first; `start_after` is the (price, offer_id) the previous page ended on
+    CollectionOffers {
+        denom: String,
+        start_after: Option<(Uint128, u64)>,
+        limit: Option<u32>,
+    },
+    /// collection offers made by an address, paged like OffersByNft
+    CollectionOffersByOfferer {
+        offerer: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    Stats {},
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ListingResponse {
+    pub listing_id: u64,
+    pub kind: ListingKind,
     pub token_id: String,
     pub denom: String,
-    pub creator: HumanAddr,
+    pub creator: Addr,
     pub status: BidStatus,
     pub highest_bid: Uint128,
-    pub highest_bidder: HumanAddr,
-    pub end_height: Option<u64>,
-    pub start_height: Option<u64>,
+    pub highest_bidder: Addr,
+    pub minimum_bid: Uint128,
+    pub end: Expiration,
+    pub start: Option<Expiration>,
     pub description: String,
+    pub metadata: Option<ListingMetadata>,
+    /// token URI the NFT module reported when the NFT was listed
+    pub token_uri: Option<String>,
+    pub nft_claimed: bool,
+    pub proceeds_claimed: bool,
+    /// true without a reserve; with one, false until the seller reveals a reserve the highest bid meets
+    pub reserve_met: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct ListingsResponse {
+    pub listings: Vec<ListingResponse>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -60,11 +197,78 @@ pub struct CreateListingResponse {
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
-pub struct ListingCountResponse {
+pub struct StatsResponse {
     pub listing_count: u64,
+    /// listings per status at the queried block, as the Listing query reports them
+    pub listings_by_status: Vec<(BidStatus, u64)>,
+    /// winning bids, fixed-price sales and accepted or filled offers settled so far, per payment denom
+    pub volume: Vec<Coin>,
+    /// NFTs sold through those settlements
+    pub sales: u64,
+    pub staked_tokens: Uint128,
+    /// addresses that have placed at least one bid
+    pub unique_bidders: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct TokenStakeResponse {
     pub token_balance: Uint128,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct DepositResponse {
+    pub token_balance: Uint128,
+    pub available_balance: Uint128,
+    pub locked_balance: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct AccountResponse {
+    pub token_balance: Uint128,
+    /// balance that can be withdrawn, i.e. not locked by any bid
+    pub available_balance: Uint128,
+    /// (listing_id, locked amount) for every bid still open
+    pub locked_tokens: Vec<(u64, Uint128)>,
+    /// taken out of token_balance to back open offers and collection offers;
+    /// returned to it when they are cancelled
+    pub held_balance: Uint128,
+    /// (listing_id, winning bid) for ended listings of this seller not yet claimed
+    pub pending_proceeds: Vec<(u64, Uint128)>,
+    /// ended listings this address has won whose NFT is not yet claimed
+    pub unclaimed_listings: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct CreateOfferResponse {
+    pub offer_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OfferResponse {
+    pub offer_id: u64,
+    pub offerer: Addr,
+    pub denom: String,
+    pub token_id: String,
+    pub price: Uint128,
+    pub expires_at_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct OffersResponse {
+    pub offers: Vec<OfferResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionOfferResponse {
+    pub offer_id: u64,
+    pub offerer: Addr,
+    pub denom: String,
+    pub price: Uint128,
+    pub quantity: u64,
+    pub expires_at_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct CollectionOffersResponse {
+    pub offers: Vec<CollectionOfferResponse>,
+}
@@ -0,0 +1,690 @@
+//! A small chain simulation in the spirit of cw-multi-test. `App` runs the
+//! auction contract against in-memory bank and NFT ledgers: funds sent with a
+//! message move into the contract, the `BankMsg` and `NftMsg` it emits are
+//! applied to the ledgers, and a failed transaction is rolled back as a whole.
+use crate::contract::{execute, instantiate, query};
+use crate::cosmos_msg::{
+    ApprovedResponse, NftChainMsg, NftMsg, NftQuery, OwnerResponse, TokenUriResponse,
+};
+use crate::msg::{
+    AccountResponse, CreateListingResponse, CreateOfferResponse, ExecuteMsg, InstantiateMsg,
+    ListingResponse, QueryMsg,
+};
+use crate::state::{BidStatus, Expiration};
+use crate::test_utils::{restore, snapshot};
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage};
+use cosmwasm_std::{
+    coins, from_binary, from_slice, to_binary, BankMsg, BlockInfo, Coin, CosmosMsg, Deps, DepsMut,
+    Env, Querier, QuerierResult, QuerierWrapper, QueryRequest, Response, StdError, StdResult,
+    SystemError, SystemResult, Uint128,
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+
+const TOKEN_DENOM: &str = "token";
+const NFT_DENOM: &str = "ethnft";
+const TEST_CREATOR: &str = "creator";
+const TEST_BIDDER: &str = "bidder1";
+const TEST_BIDDER_2: &str = "bidder2";
+
+// seconds per block when advancing the chain
+const BLOCK_TIME: u64 = 5;
+
+#[derive(Clone, Default)]
+struct Token {
+    owner: String,
+    token_uri: Option<String>,
+    // the operator the owner let transfer the token, cleared when it changes hands
+    approved: Option<String>,
+}
+
+// answers the contract's NFT queries from the NFT ledger
+struct AppQuerier<'a> {
+    nfts: &'a HashMap<(String, String), Token>,
+}
+
+impl Querier for AppQuerier<'_> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<NftQuery> = match from_slice(bin_request) {
+            Ok(request) => request,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: e.to_string(),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        let res = match request {
+            QueryRequest::Custom(NftQuery::Owner { denom, id }) => {
+                match self.nfts.get(&(denom, id)) {
+                    Some(token) => to_binary(&OwnerResponse {
+                        owner: token.owner.clone(),
+                    }),
+                    None => Err(StdError::not_found("NFT")),
+                }
+            }
+            QueryRequest::Custom(NftQuery::TokenUri { denom, id }) => {
+                match self.nfts.get(&(denom, id)) {
+                    Some(token) => to_binary(&TokenUriResponse {
+                        token_uri: token.token_uri.clone(),
+                    }),
+                    None => Err(StdError::not_found("NFT")),
+                }
+            }
+            QueryRequest::Custom(NftQuery::Approved { denom, id, operator }) => {
+                match self.nfts.get(&(denom, id)) {
+                    Some(token) => to_binary(&ApprovedResponse {
+                        approved: token.approved.as_ref() == Some(&operator),
+                    }),
+                    None => Err(StdError::not_found("NFT")),
+                }
+            }
+            _ => {
+                return SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: "non-NFT query".to_string(),
+                })
+            }
+        };
+        SystemResult::Ok(res.into())
+    }
+}
+
+pub struct App {
+    storage: MockStorage,
+    api: MockApi,
+    block: BlockInfo,
+    contract: String,
+    // (address, denom) -> amount
+    bank: HashMap<(String, String), u128>,
+    // (denom, id) -> token
+    nfts: HashMap<(String, String), Token>,
+}
+
+impl App {
+    // a chain with the auction contract instantiated by TEST_CREATOR
+    pub fn new() -> Self {
+        let env = mock_env();
+        let mut app = App {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            block: env.block,
+            contract: env.contract.address.to_string(),
+            bank: HashMap::new(),
+            nfts: HashMap::new(),
+        };
+        let msg = InstantiateMsg {
+            denom: TOKEN_DENOM.to_string(),
+            min_duration: None,
+            max_duration: None,
+            max_start_delay: None,
+        };
+        let env = app.env();
+        let info = mock_info(TEST_CREATOR, &[]);
+        let querier = AppQuerier { nfts: &app.nfts };
+        let deps = DepsMut {
+            storage: &mut app.storage,
+            api: &app.api,
+            querier: QuerierWrapper::new(&querier),
+        };
+        instantiate(deps, env, info, msg).unwrap();
+        app
+    }
+
+    pub fn contract(&self) -> &str {
+        &self.contract
+    }
+
+    fn env(&self) -> Env {
+        let mut env = mock_env();
+        env.block = self.block.clone();
+        env
+    }
+
+    pub fn height(&self) -> u64 {
+        self.block.height
+    }
+
+    pub fn advance_blocks(&mut self, blocks: u64) {
+        self.block.height += blocks;
+        self.block.time = self.block.time.plus_seconds(blocks * BLOCK_TIME);
+    }
+
+    pub fn mint_tokens(&mut self, address: &str, amount: u128) {
+        *self
+            .bank
+            .entry((address.to_string(), TOKEN_DENOM.to_string()))
+            .or_default() += amount;
+    }
+
+    pub fn balance(&self, address: &str) -> u128 {
+        self.bank
+            .get(&(address.to_string(), TOKEN_DENOM.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn mint_nft(&mut self, owner: &str, id: &str) {
+        let token = Token {
+            owner: owner.to_string(),
+            token_uri: Some(format!("ipfs://{}/{}", NFT_DENOM, id)),
+            approved: None,
+        };
+        self.nfts
+            .insert((NFT_DENOM.to_string(), id.to_string()), token);
+    }
+
+    pub fn nft_owner(&self, id: &str) -> Option<String> {
+        self.nfts
+            .get(&(NFT_DENOM.to_string(), id.to_string()))
+            .map(|token| token.owner.clone())
+    }
+
+    // a transfer the holder signs directly with the NFT module
+    pub fn transfer_nft(&mut self, sender: &str, recipient: &str, id: &str) -> StdResult<()> {
+        self.apply_nft_msg(NftMsg::Transfer {
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            denom: NFT_DENOM.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    // the holder approves the contract to transfer NFT `id`, signed directly with the NFT module
+    pub fn approve_nft(&mut self, sender: &str, id: &str) -> StdResult<()> {
+        self.apply_nft_msg(NftMsg::Approve {
+            sender: sender.to_string(),
+            operator: self.contract.clone(),
+            denom: NFT_DENOM.to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    // runs one transaction; on any error the contract storage and both ledgers
+    // are left as they were
+    pub fn execute(
+        &mut self,
+        sender: &str,
+        msg: ExecuteMsg,
+        funds: &[Coin],
+    ) -> StdResult<Response<NftChainMsg>> {
+        let storage = snapshot(&self.storage);
+        let bank = self.bank.clone();
+        let nfts = self.nfts.clone();
+        let res = self.try_execute(sender, msg, funds);
+        if res.is_err() {
+            self.storage = restore(storage);
+            self.bank = bank;
+            self.nfts = nfts;
+        }
+        res
+    }
+
+    fn try_execute(
+        &mut self,
+        sender: &str,
+        msg: ExecuteMsg,
+        funds: &[Coin],
+    ) -> StdResult<Response<NftChainMsg>> {
+        let contract = self.contract.clone();
+        for coin in funds {
+            self.send(sender, &contract, coin)?;
+        }
+
+        let env = self.env();
+        let info = mock_info(sender, funds);
+        let querier = AppQuerier { nfts: &self.nfts };
+        let deps = DepsMut {
+            storage: &mut self.storage,
+            api: &self.api,
+            querier: QuerierWrapper::new(&querier),
+        };
+        let res = execute(deps, env, info, msg)?;
+
+        for sub_msg in &res.messages {
+            match &sub_msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    for coin in amount {
+                        self.send(&contract, to_address, coin)?;
+                    }
+                }
+                // the contract signs its NFT messages itself; it moves anyone else's
+                // NFT only as the operator they approved
+                CosmosMsg::Custom(NftChainMsg::Nft(msg)) => {
+                    if msg_sender(msg) != contract {
+                        return Err(StdError::generic_err(
+                            "NFT message not sent by the contract",
+                        ));
+                    }
+                    self.apply_nft_msg(msg.clone())?;
+                }
+                msg => {
+                    return Err(StdError::generic_err(format!(
+                        "Unsupported message: {:?}",
+                        msg
+                    )))
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn query<T: DeserializeOwned>(&self, msg: QueryMsg) -> StdResult<T> {
+        let querier = AppQuerier { nfts: &self.nfts };
+        let deps = Deps {
+            storage: &self.storage,
+            api: &self.api,
+            querier: QuerierWrapper::new(&querier),
+        };
+        from_binary(&query(deps, self.env(), msg)?)
+    }
+
+    fn send(&mut self, from: &str, to: &str, coin: &Coin) -> StdResult<()> {
+        let amount = coin.amount.u128();
+        let from_balance = self
+            .bank
+            .entry((from.to_string(), coin.denom.clone()))
+            .or_default();
+        if *from_balance < amount {
+            return Err(StdError::generic_err(format!(
+                "{} has insufficient funds",
+                from
+            )));
+        }
+        *from_balance -= amount;
+        *self
+            .bank
+            .entry((to.to_string(), coin.denom.clone()))
+            .or_default() += amount;
+        Ok(())
+    }
+
+    fn apply_nft_msg(&mut self, msg: NftMsg) -> StdResult<()> {
+        match msg {
+            NftMsg::Transfer {
+                sender,
+                recipient,
+                denom,
+                id,
+            } => {
+                let token = self.owned_token(&sender, denom, id)?;
+                token.owner = recipient;
+                token.approved = None;
+            }
+            NftMsg::Approve {
+                sender,
+                operator,
+                denom,
+                id,
+            } => {
+                let token = self.owned_token(&sender, denom, id)?;
+                token.approved = Some(operator);
+            }
+            NftMsg::TransferFrom {
+                sender,
+                owner,
+                recipient,
+                denom,
+                id,
+            } => {
+                let token = self.owned_token(&owner, denom, id)?;
+                if token.approved.as_ref() != Some(&sender) {
+                    return Err(StdError::generic_err("NFT transfer is not approved"));
+                }
+                token.owner = recipient;
+                token.approved = None;
+            }
+            NftMsg::Mint {
+                recipient,
+                denom,
+                id,
+                token_uri,
+                ..
+            } => {
+                let key = (denom, id);
+                if self.nfts.contains_key(&key) {
+                    return Err(StdError::generic_err("NFT already exists"));
+                }
+                let token = Token {
+                    owner: recipient,
+                    token_uri,
+                    approved: None,
+                };
+                self.nfts.insert(key, token);
+            }
+            NftMsg::Burn { sender, denom, id } => {
+                self.owned_token(&sender, denom.clone(), id.clone())?;
+                self.nfts.remove(&(denom, id));
+            }
+            NftMsg::EditMetadata {
+                sender,
+                denom,
+                id,
+                token_uri,
+            } => {
+                let token = self.owned_token(&sender, denom, id)?;
+                token.token_uri = token_uri;
+            }
+        }
+        Ok(())
+    }
+
+    fn owned_token(&mut self, owner: &str, denom: String, id: String) -> StdResult<&mut Token> {
+        match self.nfts.get_mut(&(denom, id)) {
+            Some(token) if token.owner == owner => Ok(token),
+            Some(_) => Err(StdError::generic_err("NFT is not owned by the sender")),
+            None => Err(StdError::not_found("NFT")),
+        }
+    }
+}
+
+fn msg_sender(msg: &NftMsg) -> &str {
+    match msg {
+        NftMsg::Transfer { sender, .. }
+        | NftMsg::Approve { sender, .. }
+        | NftMsg::TransferFrom { sender, .. }
+        | NftMsg::Mint { sender, .. }
+        | NftMsg::Burn { sender, .. }
+        | NftMsg::EditMetadata { sender, .. } => sender,
+    }
+}
+
+// TEST_CREATOR approves the contract for NFT `id` and lists it, which takes it into escrow
+fn list(app: &mut App, id: &str, minimum_bid: u128) -> u64 {
+    app.approve_nft(TEST_CREATOR, id).unwrap();
+    let msg = ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: id.to_string(),
+        minimum_bid: Uint128::from(minimum_bid),
+        start: None,
+        end: Some(Expiration::AtHeight(app.height() + 1_000)),
+        description: "test".to_string(),
+        metadata: None,
+        reserve_commitment: None,
+    };
+    let res = app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    let value: CreateListingResponse = from_binary(&res.data.unwrap()).unwrap();
+    value.listing_id
+}
+
+fn bid(
+    app: &mut App,
+    bidder: &str,
+    listing_id: u64,
+    price: u128,
+) -> StdResult<Response<NftChainMsg>> {
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(price),
+        refund_surplus: None,
+    };
+    app.execute(bidder, msg, &coins(price, TOKEN_DENOM))
+}
+
+fn close(app: &mut App, listing_id: u64) -> StdResult<Response<NftChainMsg>> {
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    app.execute(TEST_CREATOR, msg, &[])
+}
+
+fn withdraw_all(app: &mut App, address: &str) {
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    app.execute(address, msg, &[]).unwrap();
+}
+
+#[test]
+fn auction_moves_nft_and_tokens() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_tokens(TEST_BIDDER, 100);
+    app.mint_tokens(TEST_BIDDER_2, 100);
+
+    let listing_id = list(&mut app, "n1", 10);
+    assert_eq!(Some(app.contract().to_string()), app.nft_owner("n1"));
+    bid(&mut app, TEST_BIDDER, listing_id, 20).unwrap();
+    bid(&mut app, TEST_BIDDER_2, listing_id, 30).unwrap();
+    assert_eq!(50, app.balance(app.contract()));
+
+    // bidding closes with the end height
+    app.advance_blocks(1_000);
+    app.mint_tokens("latecomer", 100);
+    let err = bid(&mut app, "latecomer", listing_id, 40).unwrap_err();
+    assert_eq!(StdError::generic_err("Listing has ended"), err);
+    assert_eq!(100, app.balance("latecomer"));
+
+    close(&mut app, listing_id).unwrap();
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    app.execute(TEST_BIDDER_2, msg, &[]).unwrap();
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    withdraw_all(&mut app, TEST_BIDDER);
+
+    assert_eq!(Some(TEST_BIDDER_2.to_string()), app.nft_owner("n1"));
+    assert_eq!(30, app.balance(TEST_CREATOR));
+    assert_eq!(100, app.balance(TEST_BIDDER));
+    assert_eq!(70, app.balance(TEST_BIDDER_2));
+    assert_eq!(0, app.balance(app.contract()));
+}
+
+#[test]
+fn rejected_auction_returns_nft() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_tokens(TEST_BIDDER, 100);
+
+    let listing_id = list(&mut app, "n1", 50);
+    bid(&mut app, TEST_BIDDER, listing_id, 20).unwrap();
+    // once bid on, the auction runs its full course
+    let err = close(&mut app, listing_id).unwrap_err();
+    assert_eq!(StdError::generic_err("Listing period has not expired."), err);
+    app.advance_blocks(999);
+    close(&mut app, listing_id).unwrap_err();
+    app.advance_blocks(1);
+    close(&mut app, listing_id).unwrap();
+    let value: ListingResponse = app.query(QueryMsg::Listing { listing_id }).unwrap();
+    assert_eq!(BidStatus::Rejected, value.status);
+
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    withdraw_all(&mut app, TEST_BIDDER);
+
+    assert_eq!(Some(TEST_CREATOR.to_string()), app.nft_owner("n1"));
+    assert_eq!(100, app.balance(TEST_BIDDER));
+    assert_eq!(0, app.balance(app.contract()));
+}
+
+#[test]
+fn fixed_price_sale_and_relisting() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_tokens(TEST_BIDDER, 100);
+
+    let contract = app.contract().to_string();
+    app.approve_nft(TEST_CREATOR, "n1").unwrap();
+    let msg = ExecuteMsg::ListFixedPrice {
+        denom: NFT_DENOM.to_string(),
+        token_id: "n1".to_string(),
+        price: Uint128::from(40u128),
+        description: "test".to_string(),
+        metadata: None,
+    };
+    let res = app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    let value: CreateListingResponse = from_binary(&res.data.unwrap()).unwrap();
+
+    let msg = ExecuteMsg::Buy {
+        listing_id: value.listing_id,
+        price: Uint128::from(40u128),
+    };
+    app.execute(TEST_BIDDER, msg, &coins(40, TOKEN_DENOM))
+        .unwrap();
+    assert_eq!(Some(TEST_BIDDER.to_string()), app.nft_owner("n1"));
+
+    // the buyer can put the NFT straight back up for auction, once they approve the contract
+    app.approve_nft(TEST_BIDDER, "n1").unwrap();
+    let msg = ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: "n1".to_string(),
+        minimum_bid: Uint128::from(1u128),
+        start: None,
+        end: None,
+        description: "resale".to_string(),
+        metadata: None,
+        reserve_commitment: None,
+    };
+    app.execute(TEST_BIDDER, msg, &[]).unwrap();
+
+    let msg = ExecuteMsg::ClaimProceeds {
+        listing_id: value.listing_id,
+    };
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    assert_eq!(40, app.balance(TEST_CREATOR));
+    assert_eq!(60, app.balance(TEST_BIDDER));
+    assert_eq!(Some(contract.clone()), app.nft_owner("n1"));
+    assert_eq!(0, app.balance(&contract));
+}
+
+#[test]
+fn collection_offer_is_filled_from_the_holder() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_nft(TEST_CREATOR, "n2");
+    app.mint_tokens(TEST_BIDDER, 100);
+
+    let msg = ExecuteMsg::MakeCollectionOffer {
+        denom: NFT_DENOM.to_string(),
+        price: Uint128::from(20u128),
+        quantity: 3,
+        expires_at_height: None,
+    };
+    let res = app
+        .execute(TEST_BIDDER, msg, &coins(60, TOKEN_DENOM))
+        .unwrap();
+    let value: CreateOfferResponse = from_binary(&res.data.unwrap()).unwrap();
+
+    let contract = app.contract().to_string();
+    app.approve_nft(TEST_CREATOR, "n1").unwrap();
+    app.approve_nft(TEST_CREATOR, "n2").unwrap();
+    let msg = ExecuteMsg::FillCollectionOffer {
+        offer_id: value.offer_id,
+        token_ids: vec!["n1".to_string(), "n2".to_string()],
+    };
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    assert_eq!(Some(TEST_BIDDER.to_string()), app.nft_owner("n1"));
+    assert_eq!(Some(TEST_BIDDER.to_string()), app.nft_owner("n2"));
+
+    // the seller is paid into their contract balance and pulls it out
+    withdraw_all(&mut app, TEST_CREATOR);
+    assert_eq!(40, app.balance(TEST_CREATOR));
+
+    // the unfilled token goes back to the offerer's balance on cancel
+    let msg = ExecuteMsg::CancelCollectionOffer {
+        offer_id: value.offer_id,
+    };
+    app.execute(TEST_BIDDER, msg, &[]).unwrap();
+    let value: AccountResponse = app
+        .query(QueryMsg::Account {
+            address: TEST_BIDDER.to_string(),
+        })
+        .unwrap();
+    assert_eq!(Uint128::from(20u128), value.available_balance);
+    withdraw_all(&mut app, TEST_BIDDER);
+    assert_eq!(60, app.balance(TEST_BIDDER));
+    assert_eq!(0, app.balance(&contract));
+}
+
+#[test]
+fn failed_transactions_are_rolled_back() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_tokens(TEST_BIDDER, 10);
+    let listing_id = list(&mut app, "n1", 5);
+
+    // the bank refuses funds the bidder does not have
+    let err = bid(&mut app, TEST_BIDDER, listing_id, 20).unwrap_err();
+    assert_eq!(StdError::generic_err("bidder1 has insufficient funds"), err);
+
+    // the contract rejects the bid after the funds moved; they move back
+    bid(&mut app, TEST_BIDDER, listing_id, 6).unwrap();
+    let err = bid(&mut app, TEST_BIDDER, listing_id, 4).unwrap_err();
+    assert_eq!(
+        StdError::generic_err("Set price higher than highest bid"),
+        err
+    );
+    assert_eq!(4, app.balance(TEST_BIDDER));
+    assert_eq!(6, app.balance(app.contract()));
+
+    // an NFT someone else handed to the contract is not the sender's to list
+    app.mint_nft(TEST_BIDDER, "n2");
+    let contract = app.contract().to_string();
+    app.transfer_nft(TEST_BIDDER, &contract, "n2").unwrap();
+    let msg = ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: "n2".to_string(),
+        minimum_bid: Uint128::from(1u128),
+        start: None,
+        end: None,
+        description: "test".to_string(),
+        metadata: None,
+        reserve_commitment: None,
+    };
+    let err = app.execute(TEST_CREATOR, msg, &[]).unwrap_err();
+    assert_eq!(StdError::generic_err("NFT is not owned by the sender"), err);
+    assert_eq!(Some(contract), app.nft_owner("n2"));
+    let value: ListingResponse = app.query(QueryMsg::Listing { listing_id }).unwrap();
+    assert_eq!(Uint128::from(6u128), value.highest_bid);
+}
+
+#[test]
+fn nft_moves_only_with_the_owners_approval() {
+    let mut app = App::new();
+    app.mint_nft(TEST_CREATOR, "n1");
+    app.mint_tokens(TEST_BIDDER, 30);
+
+    // without an approval the contract can't take the NFT, and nothing moves
+    let msg = ExecuteMsg::ListFixedPrice {
+        denom: NFT_DENOM.to_string(),
+        token_id: "n1".to_string(),
+        price: Uint128::from(30u128),
+        description: "test".to_string(),
+        metadata: None,
+    };
+    let err = app.execute(TEST_CREATOR, msg.clone(), &[]).unwrap_err();
+    assert_eq!(
+        StdError::generic_err("Contract is not approved to transfer the NFT"),
+        err
+    );
+    assert_eq!(Some(TEST_CREATOR.to_string()), app.nft_owner("n1"));
+
+    // only the holder can approve it
+    let err = app.approve_nft(TEST_BIDDER, "n1").unwrap_err();
+    assert_eq!(StdError::generic_err("NFT is not owned by the sender"), err);
+
+    // the approval is used up when the NFT moves into escrow
+    app.approve_nft(TEST_CREATOR, "n1").unwrap();
+    let res = app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    let value: CreateListingResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(Some(app.contract().to_string()), app.nft_owner("n1"));
+    let msg = ExecuteMsg::Delist {
+        listing_id: value.listing_id,
+    };
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    assert_eq!(Some(TEST_CREATOR.to_string()), app.nft_owner("n1"));
+
+    let msg = ExecuteMsg::MakeOffer {
+        denom: NFT_DENOM.to_string(),
+        token_id: "n1".to_string(),
+        price: Uint128::from(30u128),
+        expires_at_height: None,
+    };
+    app.execute(TEST_BIDDER, msg, &coins(30, TOKEN_DENOM)).unwrap();
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    let err = app.execute(TEST_CREATOR, msg.clone(), &[]).unwrap_err();
+    assert_eq!(
+        StdError::generic_err("Contract is not approved to transfer the NFT"),
+        err
+    );
+    app.approve_nft(TEST_CREATOR, "n1").unwrap();
+    app.execute(TEST_CREATOR, msg, &[]).unwrap();
+    assert_eq!(Some(TEST_BIDDER.to_string()), app.nft_owner("n1"));
+}
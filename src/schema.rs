@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use cosmwasm_schema::{export_schema, schema_for};
+
+use crate::cosmos_msg::{
+    ApprovedResponse, NftChainMsg, NftMsg, NftQuery, OwnerResponse, TokenUriResponse,
+};
+use crate::msg::{
+    AccountResponse, CollectionOfferResponse, CollectionOffersResponse, CreateListingResponse,
+    CreateOfferResponse, DepositResponse, ExecuteMsg, InstantiateMsg, ListingResponse,
+    ListingsResponse, OfferResponse, OffersResponse, QueryMsg, StatsResponse, TokenStakeResponse,
+};
+use crate::state::{Listing, State};
+
+/// writes the JSON schema of every exported type into `out_dir`,
+/// used by examples/schema.rs and the drift check in tests/schema.rs
+pub fn export_schemas(out_dir: &Path) {
+    export_schema(&schema_for!(InstantiateMsg), out_dir);
+    export_schema(&schema_for!(ExecuteMsg), out_dir);
+    export_schema(&schema_for!(QueryMsg), out_dir);
+    export_schema(&schema_for!(State), out_dir);
+    export_schema(&schema_for!(Listing), out_dir);
+    export_schema(&schema_for!(ListingResponse), out_dir);
+    export_schema(&schema_for!(ListingsResponse), out_dir);
+    export_schema(&schema_for!(CreateListingResponse), out_dir);
+    export_schema(&schema_for!(TokenStakeResponse), out_dir);
+    export_schema(&schema_for!(StatsResponse), out_dir);
+    export_schema(&schema_for!(DepositResponse), out_dir);
+    export_schema(&schema_for!(AccountResponse), out_dir);
+    export_schema(&schema_for!(CreateOfferResponse), out_dir);
+    export_schema(&schema_for!(OfferResponse), out_dir);
+    export_schema(&schema_for!(OffersResponse), out_dir);
+    export_schema(&schema_for!(CollectionOfferResponse), out_dir);
+    export_schema(&schema_for!(CollectionOffersResponse), out_dir);
+    export_schema(&schema_for!(NftMsg), out_dir);
+    export_schema(&schema_for!(NftChainMsg), out_dir);
+    export_schema(&schema_for!(NftQuery), out_dir);
+    export_schema(&schema_for!(OwnerResponse), out_dir);
+    export_schema(&schema_for!(TokenUriResponse), out_dir);
+    export_schema(&schema_for!(ApprovedResponse), out_dir);
+}
@@ -1,21 +1,50 @@
-use cosmwasm_std::{CanonicalAddr, Storage, Uint128};
-use cosmwasm_storage::{
-    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
-    Singleton,
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, Coin, Empty, OverflowError, OverflowOperation, StdError, StdResult,
+    Uint128,
 };
+use cw_storage_plus::{Item, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-static CONFIG_KEY: &[u8] = b"config";
-static LISTING_KEY: &[u8] = b"listing";
-static BANK_KEY: &[u8] = b"bank";
+pub const CONFIG: Item<State> = Item::new("config");
+pub const LISTINGS: Map<u64, Listing> = Map::new("listing");
+pub const BANK: Map<&Addr, TokenManager> = Map::new("bank");
+pub const OFFERS: Map<u64, Offer> = Map::new("offer");
+pub const COLLECTION_OFFERS: Map<u64, CollectionOffer> = Map::new("collection_offer");
+// NFTs held for an open listing, keyed by (denom, token id), with the listing id
+pub const ESCROWED_NFTS: Map<(&str, &str), u64> = Map::new("escrowed_nft");
+pub const STATS: Item<Stats> = Item::new("stats");
+// every address that has bid at least once, so each is counted once in Stats
+pub const BIDDERS: Map<&Addr, Empty> = Map::new("bidder");
+// listings stored as Scheduled, with their start, so Stats can count the ones that have opened
+pub const SCHEDULED_LISTINGS: Map<u64, Expiration> = Map::new("scheduled_listing");
+// listings whose proceeds their creator may still claim, keyed by (creator, listing id);
+// an entry goes once the proceeds are claimed or the listing is rejected
+pub const CREATOR_LISTINGS: Map<(&Addr, u64), Empty> = Map::new("creator_listing");
+// auctions an address leads or has won and not claimed the NFT of, keyed by (bidder, listing id)
+pub const LEADING_BIDS: Map<(&Addr, u64), Empty> = Map::new("leading_bid");
+// open offers keyed by (denom, token id, offer id) and by (offerer, offer id)
+pub const OFFERS_BY_NFT: Map<(&str, &str, u64), Empty> = Map::new("offer_by_nft");
+pub const OFFERS_BY_OFFERER: Map<(&Addr, u64), Empty> = Map::new("offer_by_offerer");
+// open collection offers keyed by (denom, u128::MAX - price, offer id), so a
+// denom's offers range highest price first and, at one price, oldest first
+pub const COLLECTION_OFFERS_BY_PRICE: Map<(&str, u128, u64), Empty> =
+    Map::new("collection_offer_by_price");
+pub const COLLECTION_OFFERS_BY_OFFERER: Map<(&Addr, u64), Empty> =
+    Map::new("collection_offer_by_offerer");
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub denom: String,
-    pub owner: CanonicalAddr,
+    pub owner: Addr,
     pub listing_count: u64,
     pub staked_tokens: Uint128,
+    pub offer_count: u64,
+    pub collection_offer_count: u64,
+    pub min_duration: Duration,
+    pub max_duration: Duration,
+    pub max_start_delay: Duration,     // how far ahead a listing may be scheduled
 }
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -25,56 +54,197 @@ pub struct TokenManager {
     pub participated_bids: Vec<u64>,       // listing_id
 }
 
+/// running totals, updated as listings are created, bid on and settled
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Stats {
+    pub listings_by_status: Vec<(BidStatus, u64)>,
+    pub volume: Vec<Coin>,          // settled sales, per payment denom
+    pub sales: u64,                 // NFTs sold, through listings and offers
+    pub unique_bidders: u64,
+}
+
+impl Stats {
+    /// moves a listing from one status count to another; `from` is None for a new listing
+    pub fn count_status(&mut self, from: Option<&BidStatus>, to: &BidStatus) -> StdResult<()> {
+        if let Some(from) = from {
+            let count = self.status_count(from);
+            let decremented = count.checked_sub(1).ok_or_else(|| {
+                StdError::overflow(OverflowError::new(OverflowOperation::Sub, count, 1))
+            })?;
+            self.set_status_count(from, decremented);
+        }
+        let count = self.status_count(to);
+        let incremented = count.checked_add(1).ok_or_else(|| {
+            StdError::overflow(OverflowError::new(OverflowOperation::Add, count, 1))
+        })?;
+        self.set_status_count(to, incremented);
+        Ok(())
+    }
+
+    fn set_status_count(&mut self, status: &BidStatus, count: u64) {
+        match self.listings_by_status.iter_mut().find(|(s, _)| s == status) {
+            Some((_, c)) => *c = count,
+            None => self.listings_by_status.push((status.clone(), count)),
+        }
+    }
+
+    pub fn status_count(&self, status: &BidStatus) -> u64 {
+        self.listings_by_status
+            .iter()
+            .find(|(s, _)| s == status)
+            .map_or(0, |(_, count)| *count)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Bidder {
-    pub bidder: CanonicalAddr,
+    pub bidder: Addr,
     pub price: Uint128,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum BidStatus {
+    Scheduled,      // escrowed and announced, but not open for bids until its start
     InProgress,
     Tally,
     Passed,
     Rejected,
 }
 
+/// a point in the chain's life, either a block height or a block time in seconds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiration {
+    AtHeight(u64),
+    AtTime(u64),
+}
+
+impl Expiration {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        self.now(block) >= self.value()
+    }
+
+    /// the height or time this expires at
+    pub fn value(&self) -> u64 {
+        match *self {
+            Expiration::AtHeight(height) => height,
+            Expiration::AtTime(time) => time,
+        }
+    }
+
+    /// the current height or time, whichever this is measured in
+    pub fn now(&self, block: &BlockInfo) -> u64 {
+        match *self {
+            Expiration::AtHeight(_) => block.height,
+            Expiration::AtTime(_) => block.time.seconds(),
+        }
+    }
+
+    pub fn same_unit(&self, other: &Expiration) -> bool {
+        matches!(
+            (self, other),
+            (Expiration::AtHeight(_), Expiration::AtHeight(_))
+                | (Expiration::AtTime(_), Expiration::AtTime(_))
+        )
+    }
+}
+
+impl fmt::Display for Expiration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expiration::AtHeight(height) => write!(f, "expiration height: {}", height),
+            Expiration::AtTime(time) => write!(f, "expiration time: {}", time),
+        }
+    }
+}
+
+/// a span of the chain's life in both units, so it can bound either kind of Expiration
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct Duration {
+    pub blocks: u64,
+    pub seconds: u64,
+}
+
+impl Duration {
+    /// the blocks or seconds of this duration, whichever the expiration is measured in
+    pub fn in_unit_of(&self, expiration: &Expiration) -> u64 {
+        match expiration {
+            Expiration::AtHeight(_) => self.blocks,
+            Expiration::AtTime(_) => self.seconds,
+        }
+    }
+}
+
+/// display metadata of a listing; lengths are limited in characters, not bytes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListingMetadata {
+    pub title: String,
+    pub image: Option<String>,
+    pub external_url: Option<String>,
+    #[serde(default)]
+    pub attributes: Vec<MetadataAttribute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MetadataAttribute {
+    pub trait_type: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingKind {
+    Auction,
+    FixedPrice,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Listing {
+    pub kind: ListingKind,
     pub token_id: String,
     pub denom: String,
-    pub creator: CanonicalAddr,
+    pub creator: Addr,
     pub status: BidStatus,
     pub highest_bid: Uint128,
-    pub highest_bidder: CanonicalAddr,
-    pub minimum_bid : Uint128,
-    pub bidders : Vec<CanonicalAddr>,
+    pub highest_bidder: Addr,
+    pub minimum_bid : Uint128,       // asking price of a fixed-price listing
+    pub bidders : Vec<Addr>,
     pub bidders_info : Vec<Bidder>,
-    pub start_height: Option<u64>,
-    pub end_height: u64,
+    pub start: Option<Expiration>,
+    pub end: Expiration,
     pub description: String,
+    pub metadata: Option<ListingMetadata>,
+    pub token_uri: Option<String>,  // as reported by the NFT module at listing time
+    pub nft_claimed: bool,
+    pub proceeds_claimed: bool,
+    pub reserve_commitment: Option<Binary>, // sha256 of "<reserve>:<salt>"
+    pub reserve_met: bool,
 }
 
-pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, State> {
-    singleton(storage, CONFIG_KEY)
-}
-
-pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
-    singleton_read(storage, CONFIG_KEY)
-}
-
-pub fn listing<S: Storage>(storage: &mut S) -> Bucket<S, Listing> {
-    bucket(storage, LISTING_KEY)
-}
-
-pub fn listing_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Listing> {
-    bucket_read(storage, LISTING_KEY)
+impl Listing {
+    /// the status at the given block; a scheduled listing opens once its start has passed
+    pub fn status_at(&self, block: &BlockInfo) -> BidStatus {
+        match (&self.status, self.start) {
+            (BidStatus::Scheduled, Some(start)) if start.is_expired(block) => BidStatus::InProgress,
+            (status, _) => status.clone(),
+        }
+    }
 }
 
-pub fn bank<S: Storage>(storage: &mut S) -> Bucket<S, TokenManager> {
-    bucket(storage, BANK_KEY)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Offer {
+    pub offerer: Addr,
+    pub denom: String,
+    pub token_id: String,
+    pub price: Uint128,             // held out of the offerer's balance
+    pub expires_at_height: Option<u64>,
 }
 
-pub fn bank_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, TokenManager> {
-    bucket_read(storage, BANK_KEY)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CollectionOffer {
+    pub offerer: Addr,
+    pub denom: String,
+    pub price: Uint128,             // per token
+    pub quantity: u64,              // tokens still wanted
+    pub expires_at_height: Option<u64>,
 }
@@ -0,0 +1,16 @@
+//! Helpers shared by the unit tests and the multitest chain simulation.
+use cosmwasm_std::testing::MockStorage;
+use cosmwasm_std::{Order, Record, Storage};
+
+// failed transactions are reverted by the chain, so mirror that in mock storage
+pub fn snapshot(storage: &MockStorage) -> Vec<Record> {
+    storage.range(None, None, Order::Ascending).collect()
+}
+
+pub fn restore(snapshot: Vec<Record>) -> MockStorage {
+    let mut storage = MockStorage::default();
+    for (k, v) in snapshot {
+        storage.set(&k, &v);
+    }
+    storage
+}
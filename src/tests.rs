@@ -1,862 +1,3356 @@
-#[cfg(test)]
-mod tests {
-    use crate::contract::{handle, init, query, VOTING_TOKEN};
-    use crate::msg::{HandleMsg, InitMsg, ListingResponse, QueryMsg};
-    use crate::state::{config_read, State};
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
-    use cosmwasm_std::{
-        coins, from_binary, log, Api, BankMsg, Coin, CosmosMsg, Env, Extern, HandleResponse,
-        HumanAddr, StdError, Uint128,
-    };
-
-    const DEFAULT_END_HEIGHT: u64 = 100800u64;
-    const TEST_CREATOR: &str = "creator";
-    const TEST_VOTER: &str = "voter1";
-    const TEST_VOTER_2: &str = "voter2";
-
-    fn mock_init(mut deps: &mut Extern<MockStorage, MockApi, MockQuerier>) {
-        let msg = InitMsg {
-            denom: String::from(VOTING_TOKEN),
+use crate::contract::{execute, instantiate, query, reserve_hash};
+use crate::cosmos_msg::{
+    ApprovedResponse, NftChainMsg, NftMsg, NftQuery, OwnerResponse, TokenUriResponse,
+};
+use crate::msg::{
+    AccountResponse, CollectionOffersResponse, CreateListingResponse, CreateOfferResponse,
+    DepositResponse, ExecuteMsg, InstantiateMsg, ListingResponse, ListingsResponse,
+    OffersResponse, QueryMsg, ReserveReveal, StatsResponse, TokenStakeResponse,
+};
+use crate::state::{
+    BidStatus, Duration, Expiration, ListingKind, ListingMetadata, MetadataAttribute, State, Stats,
+    BANK, BIDDERS, COLLECTION_OFFERS, COLLECTION_OFFERS_BY_OFFERER, COLLECTION_OFFERS_BY_PRICE,
+    CONFIG, CREATOR_LISTINGS, ESCROWED_NFTS, LEADING_BIDS, LISTINGS, OFFERS, OFFERS_BY_NFT,
+    OFFERS_BY_OFFERER, STATS,
+};
+use crate::test_utils::{restore, snapshot};
+use cosmwasm_std::testing::{
+    mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_std::{
+    coins, from_binary, to_binary, Addr, Attribute, BankMsg, Coin, ContractResult, CosmosMsg,
+    Empty, Env, Order, OverflowError, OverflowOperation, OwnedDeps, Response, StdError, StdResult,
+    SubMsg, SystemResult, Timestamp, Uint128,
+};
+use cw_storage_plus::Map;
+use proptest::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+const DEFAULT_END_HEIGHT: u64 = 100800u64;
+const TOKEN_DENOM: &str = "token";
+const NFT_DENOM: &str = "ethnft";
+const NFT_ID: &str = "0x42447730b00Ea074121f2429a0654A10597e3a25";
+const TEST_CREATOR: &str = "creator";
+const TEST_BIDDER: &str = "bidder1";
+const TEST_BIDDER_2: &str = "bidder2";
+
+type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier<NftQuery>, NftQuery>;
+
+thread_local! {
+    // the NFT module's ledger as the mock querier reports it; a token not in it
+    // belongs to the default owner passed to mock_deps_with_nft_owner, who has
+    // approved the contract to transfer it
+    static NFT_OWNERS: RefCell<HashMap<(String, String), String>> = RefCell::new(HashMap::new());
+    // the operator approved for each token in the ledger, until it changes hands
+    static NFT_APPROVALS: RefCell<HashMap<(String, String), String>> = RefCell::new(HashMap::new());
+}
+
+// mock dependencies whose NFT module reports every NFT as owned by the creator
+fn mock_deps() -> MockDeps {
+    mock_deps_with_nft_owner(TEST_CREATOR)
+}
+
+fn mock_deps_with_nft_owner(owner: &str) -> MockDeps {
+    NFT_OWNERS.with(|owners| owners.borrow_mut().clear());
+    NFT_APPROVALS.with(|approvals| approvals.borrow_mut().clear());
+    let owner = owner.to_string();
+    let querier = MockQuerier::new(&[]).with_custom_handler(move |query| {
+        let res = match query {
+            NftQuery::Owner { denom, id } => to_binary(&OwnerResponse {
+                owner: nft_owner(denom, id).unwrap_or_else(|| owner.clone()),
+            }),
+            NftQuery::TokenUri { denom, id } => to_binary(&TokenUriResponse {
+                token_uri: Some(format!("ipfs://{}/{}", denom, id)),
+            }),
+            NftQuery::Approved { denom, id, operator } => to_binary(&ApprovedResponse {
+                approved: nft_approved(denom, id, operator),
+            }),
         };
+        SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+    });
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier,
+        custom_query_type: PhantomData,
+    }
+}
+
+fn nft_owner(denom: &str, id: &str) -> Option<String> {
+    NFT_OWNERS.with(|owners| owners.borrow().get(&(denom.to_string(), id.to_string())).cloned())
+}
 
-        let env = mock_env(TEST_CREATOR, &coins(2, &msg.denom));
-        let _res = init(&mut deps, env, msg).expect("contract successfully handles InitMsg");
+fn nft_approved(denom: &str, id: &str, operator: &str) -> bool {
+    let key = (denom.to_string(), id.to_string());
+    match NFT_APPROVALS.with(|approvals| approvals.borrow().get(&key).cloned()) {
+        Some(approved) => approved == operator,
+        None => {
+            operator == MOCK_CONTRACT_ADDR && NFT_OWNERS.with(|owners| !owners.borrow().contains_key(&key))
+        }
     }
+}
+
+// hands an NFT to `owner`, which clears any approval given by the previous owner
+fn set_nft_owner(id: &str, owner: &str) {
+    let key = (NFT_DENOM.to_string(), id.to_string());
+    NFT_APPROVALS.with(|approvals| approvals.borrow_mut().remove(&key));
+    NFT_OWNERS.with(|owners| owners.borrow_mut().insert(key, owner.to_string()));
+}
+
+// the owner approves the contract to transfer NFT `id`, as they would with the NFT module
+fn approve_nft(id: &str) {
+    NFT_APPROVALS.with(|approvals| {
+        approvals.borrow_mut().insert(
+            (NFT_DENOM.to_string(), id.to_string()),
+            MOCK_CONTRACT_ADDR.to_string(),
+        )
+    });
+}
 
-    fn mock_env_height(sender: &str, sent: &[Coin], height: u64, time: u64) -> Env {
-        let mut env = mock_env(sender, sent);
-        env.block.height = height;
-        env.block.time = time;
-        env
+fn mock_init(deps: &mut MockDeps) {
+    let msg = init_msg();
+    let info = mock_info(TEST_CREATOR, &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg)
+        .expect("contract successfully handles InstantiateMsg");
+}
+
+fn mock_env_height(height: u64, time: u64) -> Env {
+    let mut env = mock_env();
+    env.block.height = height;
+    env.block.time = Timestamp::from_seconds(time);
+    env
+}
+
+// the first block at which a listing with the default end has ended
+fn ended_env() -> Env {
+    let env = mock_env();
+    mock_env_height(env.block.height + DEFAULT_END_HEIGHT, env.block.time.seconds())
+}
+
+fn init_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        denom: String::from(TOKEN_DENOM),
+        min_duration: None,
+        max_duration: None,
+        max_start_delay: None,
     }
+}
 
-    fn init_msg() -> InitMsg {
-        InitMsg {
-            denom: String::from(VOTING_TOKEN),
+fn list_msg(minimum_bid: u128, end: Option<Expiration>) -> ExecuteMsg {
+    ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        minimum_bid: Uint128::from(minimum_bid),
+        start: None,
+        end,
+        description: "test".to_string(),
+        metadata: None,
+        reserve_commitment: None,
+    }
+}
+
+// lists the default NFT as TEST_CREATOR and returns the listing id
+fn mock_listing(deps: &mut MockDeps, minimum_bid: u128) -> u64 {
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = with_next_nft(deps, list_msg(minimum_bid, None));
+    execute(deps.as_mut(), mock_env(), info, msg).expect("contract successfully handles List");
+    CONFIG.load(&deps.storage).unwrap().listing_count
+}
+
+// each listing escrows its own NFT: NFT_ID for the first, a fresh id after that
+fn with_next_nft(deps: &MockDeps, mut msg: ExecuteMsg) -> ExecuteMsg {
+    let listing_count = CONFIG.load(&deps.storage).unwrap().listing_count;
+    if listing_count > 0 {
+        if let ExecuteMsg::List { token_id, .. } | ExecuteMsg::ListFixedPrice { token_id, .. } =
+            &mut msg
+        {
+            *token_id = format!("nft-{}", listing_count + 1);
         }
     }
+    msg
+}
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(20, &[]);
-
-        let msg = init_msg();
-        let env = mock_env(TEST_CREATOR, &coins(2, VOTING_TOKEN));
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-
-        let state = config_read(&mut deps.storage).load().unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: deps
-                    .api
-                    .canonical_address(&HumanAddr::from(TEST_CREATOR))
-                    .unwrap(),
-                listing_count: 0,
-                staked_tokens: Uint128::zero(),
-            }
-        );
+fn query_account(
+    deps: &MockDeps,
+    address: &str,
+    env: Env,
+) -> AccountResponse {
+    let msg = QueryMsg::Account {
+        address: address.to_string(),
+    };
+    from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap()
+}
+
+fn assert_generic_err<T: std::fmt::Debug>(res: Result<T, StdError>, expected: &str) {
+    match res {
+        Ok(_) => panic!("Must return error"),
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, expected),
+        Err(e) => panic!("Unexpected error: {:?}", e),
     }
+}
 
-    #[test]
-    fn listing_not_found() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+// the message moving an NFT of NFT_DENOM out of the contract
+fn nft_transfer(recipient: &str, id: &str) -> SubMsg<NftChainMsg> {
+    SubMsg::new(CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::Transfer {
+        sender: MOCK_CONTRACT_ADDR.to_string(),
+        recipient: recipient.to_string(),
+        denom: NFT_DENOM.to_string(),
+        id: id.to_string(),
+    })))
+}
 
-        let res = query(&deps, QueryMsg::Listing { listing_id: 1 });
+// the message the contract sends, as approved operator, to move `owner`'s NFT
+fn nft_transfer_from(owner: &str, recipient: &str, id: &str) -> SubMsg<NftChainMsg> {
+    SubMsg::new(CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::TransferFrom {
+        sender: MOCK_CONTRACT_ADDR.to_string(),
+        owner: owner.to_string(),
+        recipient: recipient.to_string(),
+        denom: NFT_DENOM.to_string(),
+        id: id.to_string(),
+    })))
+}
 
-        match res {
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Listing does not exist"),
-            Err(e) => panic!("Unexpected error: {:?}", e),
-            _ => panic!("Must return error"),
-        }
+// the message paying out `amount` of the staking denom
+fn bank_send(to_address: &str, amount: u128) -> SubMsg<NftChainMsg> {
+    SubMsg::new(BankMsg::Send {
+        to_address: to_address.to_string(),
+        amount: coins(amount, TOKEN_DENOM),
+    })
+}
+
+fn bid_msg(listing_id: u64, price: u128) -> ExecuteMsg {
+    ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(price),
+        refund_surplus: None,
     }
+}
 
-    #[test]
-    fn fails_create_listing_invalid_quorum_percentage() {
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env("voter", &coins(11, VOTING_TOKEN));
+fn close_msg(listing_id: u64) -> ExecuteMsg {
+    ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    }
+}
 
-        let msg = create_listing_msg(101, "test".to_string(), None, None);
+fn token_balance(deps: &MockDeps, address: &str) -> Uint128 {
+    let msg = QueryMsg::TokenStake {
+        address: address.to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let value: TokenStakeResponse = from_binary(&res).unwrap();
+    value.token_balance
+}
 
-        let res = handle(&mut deps, env, msg);
+#[test]
+fn proper_initialization() {
+    let mut deps = mock_deps();
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = instantiate(deps.as_mut(), mock_env(), info, init_msg()).unwrap();
+    assert_eq!(0, res.messages.len());
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+    let state: State = from_binary(&res).unwrap();
+    assert_eq!(TOKEN_DENOM, state.denom);
+    assert_eq!(TEST_CREATOR, state.owner);
+    assert_eq!(0, state.listing_count);
+    assert_eq!(Uint128::zero(), state.staked_tokens);
+    assert_eq!(state, CONFIG.load(&deps.storage).unwrap());
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "quorum_percentage must be 0 to 100")
-            }
-            Err(e) => panic!("Unexpected error: {:?}", e),
+#[test]
+fn happy_days_create_listing() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None)).unwrap();
+    // the contract takes the NFT from the creator
+    assert_eq!(res.messages, vec![nft_transfer_from(TEST_CREATOR, MOCK_CONTRACT_ADDR, NFT_ID)]);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute { key: "action".to_string(), value: "create_listing".to_string() },
+            Attribute { key: "listing_id".to_string(), value: "1".to_string() },
+        ]
+    );
+    let value: CreateListingResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(1, value.listing_id);
+
+    let value = query_listing(&deps, 1);
+    assert_eq!(ListingKind::Auction, value.kind);
+    assert_eq!(NFT_DENOM, value.denom);
+    assert_eq!(NFT_ID, value.token_id);
+    assert_eq!(TEST_CREATOR, value.creator);
+    assert_eq!(TEST_CREATOR, value.highest_bidder);
+    assert_eq!(BidStatus::InProgress, value.status);
+    assert_eq!(Uint128::zero(), value.highest_bid);
+    assert_eq!(Uint128::from(10u128), value.minimum_bid);
+    assert_eq!(
+        Expiration::AtHeight(mock_env().block.height + DEFAULT_END_HEIGHT),
+        value.end
+    );
+    assert_eq!(1, CONFIG.load(&deps.storage).unwrap().listing_count);
+}
+
+#[test]
+fn fails_create_listing_invalid_description() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let cases = vec![
+        ("a".to_string(), "Description too short"),
+        ("a".repeat(65), "Description too long"),
+    ];
+    for (description, expected) in cases {
+        let mut msg = list_msg(10, None);
+        if let ExecuteMsg::List { description: d, .. } = &mut msg {
+            *d = description;
         }
+        let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+        assert_generic_err(res, expected);
     }
+}
 
-    #[test]
-    fn fails_create_listing_invalid_description() {
-        let mut deps = mock_dependencies(20, &[]);
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
+#[test]
+fn happy_days_bid() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 20)).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute { key: "action".to_string(), value: "bidden".to_string() },
+            Attribute { key: "listing_id".to_string(), value: listing_id.to_string() },
+        ]
+    );
+
+    let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    assert_eq!(vec![Addr::unchecked(TEST_BIDDER)], a_listing.bidders);
+    assert_eq!(Uint128::from(20u128), a_listing.highest_bid);
+    assert_eq!(TEST_BIDDER, a_listing.highest_bidder);
+
+    let token_manager = BANK.load(&deps.storage, &Addr::unchecked(TEST_BIDDER)).unwrap();
+    assert_eq!(Uint128::from(20u128), token_manager.token_balance);
+    assert_eq!(vec![(listing_id, Uint128::from(20u128))], token_manager.locked_tokens);
+    assert_eq!(vec![listing_id], token_manager.participated_bids);
+}
 
-        let msg = create_listing_msg(30, "a".to_string(), None, None);
+#[test]
+fn fails_bid_twice_or_below_highest() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        match handle(&mut deps, env.clone(), msg) {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Description too short"),
-            Err(_) => panic!("Unknown error"),
-        }
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 20)).unwrap();
 
-        let msg = create_listing_msg(
-            100,
-            "01234567890123456789012345678901234567890123456789012345678901234".to_string(),
-            None,
-            None,
-        );
+    let info = mock_info(TEST_BIDDER_2, &coins(20, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 20));
+    assert_generic_err(res, "Set price higher than highest bid");
+
+    let info = mock_info(TEST_BIDDER, &coins(30, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 30));
+    assert_generic_err(res, "User has already bidden.");
+}
+
+#[test]
+fn happy_days_end_listing() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 20)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(30, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 30)).unwrap();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), ended_env(), info, close_msg(listing_id)).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes[2..],
+        [
+            Attribute { key: "rejected_reason".to_string(), value: "".to_string() },
+            Attribute { key: "passed".to_string(), value: "true".to_string() },
+        ]
+    );
+
+    let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    assert_eq!(BidStatus::Passed, a_listing.status);
+    assert_eq!(TEST_BIDDER_2, a_listing.highest_bidder);
+
+    // the winning bid left the winner's balance, the outbid stake is unlocked
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER_2));
+    assert_eq!(Uint128::from(20u128), token_balance(&deps, TEST_BIDDER));
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(20u128), value.available_balance);
+    assert!(value.locked_tokens.is_empty());
+    assert_eq!(Uint128::from(20u128), CONFIG.load(&deps.storage).unwrap().staked_tokens);
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), ended_env(), info, close_msg(listing_id));
+    assert_generic_err(res, "Listing is not in progress");
+}
+
+#[test]
+fn fails_end_listing_before_end() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 20)).unwrap();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, close_msg(listing_id));
+    assert_generic_err(res, "Listing period has not expired.");
+
+    let env = mock_env();
+    let before_end =
+        mock_env_height(env.block.height + DEFAULT_END_HEIGHT - 1, env.block.time.seconds());
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), before_end, info, close_msg(listing_id));
+    assert_generic_err(res, "Listing period has not expired.");
+
+    // the end height itself closes the window
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), ended_env(), info, close_msg(listing_id)).unwrap();
+    let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    assert_eq!(BidStatus::Passed, a_listing.status);
+}
+
+#[test]
+fn end_listing_on_end_time() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+    let now = env.block.time.seconds();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = list_msg(10, Some(Expiration::AtTime(now + 7_200)));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(1, 20)).unwrap();
+
+    // block height does not matter for a listing ending on block time
+    let info = mock_info(TEST_CREATOR, &[]);
+    let late_height = mock_env_height(env.block.height + DEFAULT_END_HEIGHT, now + 7_199);
+    let res = execute(deps.as_mut(), late_height, info, close_msg(1));
+    assert_generic_err(res, "Listing period has not expired.");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let on_time = mock_env_height(env.block.height, now + 7_200);
+    execute(deps.as_mut(), on_time, info, close_msg(1)).unwrap();
+    assert_eq!(BidStatus::Passed, LISTINGS.load(&deps.storage, 1).unwrap().status);
+}
+
+#[test]
+fn end_listing_without_bids_early() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+
+    // nobody was promised the window, so the creator can take the NFT back
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), mock_env(), info, close_msg(listing_id)).unwrap();
+    let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    assert_eq!(BidStatus::Rejected, a_listing.status);
+    assert_eq!(TEST_CREATOR, a_listing.highest_bidder);
+}
+
+#[test]
+fn fails_create_listing_default_end_overflow() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        match handle(&mut deps, env.clone(), msg) {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Description too long"),
-            Err(_) => panic!("Unknown error"),
+    for start in [Expiration::AtHeight(u64::MAX), Expiration::AtTime(u64::MAX)] {
+        let mut msg = list_msg(10, None);
+        if let ExecuteMsg::List { start: msg_start, .. } = &mut msg {
+            *msg_start = Some(start);
         }
+        let info = mock_info(TEST_CREATOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert_generic_err(res, "Listing starts too far in the future");
     }
+}
 
-    fn create_listing_msg(
-        quorum_percentage: u8,
-        description: String,
-        start_height: Option<u64>,
-        end_height: Option<u64>,
-    ) -> HandleMsg {
-        let msg = HandleMsg::CreateListing {
-            quorum_percentage: Some(quorum_percentage),
-            description,
-            start_height,
-            end_height,
-        };
-        msg
-    }
+#[test]
+fn fails_end_listing_early_not_creator() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-    #[test]
-    fn happy_days_create_listing() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
-
-        let quorum = 30;
-        let msg = create_listing_msg(quorum, "test".to_string(), None, None);
-
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_create_listing_result(
-            1,
-            quorum,
-            DEFAULT_END_HEIGHT,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
-    }
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, close_msg(listing_id));
+    assert_generic_err(res, "User is not the creator of the listing.");
+}
 
-    #[test]
-    fn create_listing_no_quorum() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
-
-        let quorum = 0;
-        let msg = create_listing_msg(quorum, "test".to_string(), None, None);
-
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_create_listing_result(
-            1,
-            quorum,
-            DEFAULT_END_HEIGHT,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
-    }
+#[test]
+fn anyone_ends_listing_after_end() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 50)).unwrap();
+
+    // the winner settles without waiting on the creator
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), ended_env(), info, close_msg(listing_id)).unwrap();
+    let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    assert_eq!(BidStatus::Passed, a_listing.status);
+    assert_eq!(TEST_BIDDER, a_listing.highest_bidder);
+
+    // with a hidden reserve nobody revealed, the listing is rejected and the
+    // bid unlocked
+    let listing_id = mock_reserve_listing(&mut deps, 10, 50);
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    execute(deps.as_mut(), ended_env(), info, close_msg(listing_id)).unwrap();
+    let value = query_listing(&deps, listing_id);
+    assert_eq!(BidStatus::Rejected, value.status);
+    assert_eq!(TEST_CREATOR.to_string(), value.highest_bidder);
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(50u128), value.available_balance);
+}
 
-    #[test]
-    fn fails_end_listing_before_end_height() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
+#[test]
+fn happy_days_withdraw_tokens() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let msg = create_listing_msg(0, "test".to_string(), None, Some(10001));
+    let info = mock_info(TEST_BIDDER, &coins(11, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
 
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_create_listing_result(1, 0, 10001, 0, TEST_CREATOR, handle_res, &mut deps);
+    let msg = ExecuteMsg::WithdrawTokens {
+        amount: Some(Uint128::from(4u128)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![bank_send(TEST_BIDDER, 4)]);
+    assert_eq!(Uint128::from(7u128), token_balance(&deps, TEST_BIDDER));
+
+    // without an amount the whole balance is withdrawn
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![bank_send(TEST_BIDDER, 7)]);
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER));
+    assert_eq!(Uint128::zero(), CONFIG.load(&deps.storage).unwrap().staked_tokens);
+}
 
-        let res = query(&deps, QueryMsg::Listing { listing_id: 1 }).unwrap();
-        let value: ListingResponse = from_binary(&res).unwrap();
-        assert_eq!(Some(10001), value.end_height);
+#[test]
+fn fails_withdraw_tokens() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "Nothing staked");
+
+    // tokens locked by an open bid stay in the contract
+    let listing_id = mock_listing(&mut deps, 10);
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(listing_id, 15)).unwrap();
+    let msg = ExecuteMsg::WithdrawTokens {
+        amount: Some(Uint128::from(6u128)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "User is trying to withdraw too many tokens.");
 
-        let msg = HandleMsg::EndListing { listing_id: 1 };
+    let msg = ExecuteMsg::WithdrawTokens {
+        amount: Some(Uint128::from(5u128)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![bank_send(TEST_BIDDER, 5)]);
 
-        let handle_res = handle(&mut deps, env.clone(), msg);
+    // a zero-amount send would be rejected by the bank module
+    let msg = ExecuteMsg::WithdrawTokens {
+        amount: Some(Uint128::zero()),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "Nothing to withdraw");
+
+    // once the winning bid is paid out of it, the balance is empty
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), ended_env(), info, close_msg(listing_id)).unwrap();
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER));
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "Nothing to withdraw");
+}
 
-        match handle_res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "Voting period has not expired.")
-            }
-            Err(e) => panic!("Unexpected error: {:?}", e),
+#[test]
+fn fails_deposit_overflow() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(u128::MAX, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    let info = mock_info(TEST_BIDDER, &coins(1, TOKEN_DENOM));
+    match execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}) {
+        Err(StdError::Overflow { source, .. }) => {
+            assert_eq!(source.operation, OverflowOperation::Add)
         }
+        res => panic!("Unexpected result: {:?}", res),
     }
+}
 
-    #[test]
-    fn happy_days_end_listing() {
-        const POLL_END_HEIGHT: u64 = 1000;
-        const POLL_ID: u64 = 1;
-        let stake_amount = 1000;
-
-        let mut deps = mock_dependencies(20, &coins(1000, VOTING_TOKEN));
-        mock_init(&mut deps);
-        let mut creator_env = mock_env_height(
-            TEST_CREATOR,
-            &coins(2, VOTING_TOKEN),
-            POLL_END_HEIGHT,
-            10000,
-        );
+#[test]
+fn token_stake_query() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER));
 
-        let msg = create_listing_msg(
-            0,
-            "test".to_string(),
-            None,
-            Some(creator_env.block.height + 1),
-        );
+    let info = mock_info(TEST_BIDDER, &coins(11, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    assert_eq!(Uint128::from(11u128), token_balance(&deps, TEST_BIDDER));
+}
 
-        let handle_res = handle(&mut deps, creator_env.clone(), msg).unwrap();
+#[test]
+fn fails_list_nft_already_in_escrow() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        assert_create_listing_result(
-            1,
-            0,
-            creator_env.block.height + 1,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
+    // the NFT is still escrowed for its first listing, so it can't be listed a second time
+    mock_listing(&mut deps, 10);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None));
+    assert_generic_err(res, "NFT is already in escrow");
+}
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(stake_amount, VOTING_TOKEN));
+#[test]
+fn fails_bid_without_funds_or_balance() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_stake_tokens_result(stake_amount, Some(1), handle_res, &mut deps);
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(10u128),
+        refund_surplus: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone());
+    assert_generic_err(res, "User does not have enough staked tokens.");
 
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(stake_amount),
-        };
-        let handle_res = handle(&mut deps, env.clone(), msg).unwrap();
-
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "vote_casted"),
-                log("listing_id", POLL_ID),
-                log("weight", "1000"),
-                log("voter", TEST_VOTER),
-            ]
-        );
+    // coins of a foreign denom are rejected rather than kept
+    let info = mock_info(TEST_BIDDER, &coins(100, "play money"));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Unsupported denom sent: play money");
+}
 
-        creator_env.block.height = &creator_env.block.height + 1;
+#[test]
+fn bid_from_existing_balance_without_funds() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let first = mock_listing(&mut deps, 10);
+    let second = mock_listing(&mut deps, 10);
+
+    let msg = ExecuteMsg::Bid {
+        listing_id: first,
+        price: Uint128::from(10u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(15, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // the 10 locked by the first bid cannot back the second one as well
+    let msg = ExecuteMsg::Bid {
+        listing_id: second,
+        price: Uint128::from(6u128),
+        refund_surplus: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "User does not have enough staked tokens.");
 
-        let msg = HandleMsg::EndListing { listing_id: 1 };
+    let msg = ExecuteMsg::Bid {
+        listing_id: second,
+        price: Uint128::from(5u128),
+        refund_surplus: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
 
-        let handle_res = handle(&mut deps, creator_env.clone(), msg).unwrap();
+    let bidder_raw = Addr::unchecked(TEST_BIDDER);
+    let token_manager = BANK.load(&deps.storage, &bidder_raw).unwrap();
+    assert_eq!(Uint128::from(15u128), token_manager.token_balance);
+    assert_eq!(vec![first, second], token_manager.participated_bids);
+}
 
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "end_listing"),
-                log("listing_id", "1"),
-                log("rejected_reason", ""),
-                log("passed", "true"),
-            ]
-        );
+// a bid is backed by tokens no other open bid holds, so every auction the
+// bidder wins can settle out of the balance
+#[test]
+fn every_won_bid_settles_from_its_own_lock() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let first = mock_listing(&mut deps, 5);
+    let second = mock_listing(&mut deps, 5);
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(first, 15)).unwrap();
+
+    // only the 5 not locked by the first bid is left for anything else
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), bid_msg(second, 10));
+    assert_generic_err(res, "User does not have enough staked tokens.");
+    let msg = ExecuteMsg::WithdrawTokens {
+        amount: Some(Uint128::from(10u128)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_generic_err(res, "User is trying to withdraw too many tokens.");
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(5u128), value.available_balance);
+
+    execute(deps.as_mut(), mock_env(), info, bid_msg(second, 5)).unwrap();
+
+    let ended = mock_env_height(mock_env().block.height + DEFAULT_END_HEIGHT, 0);
+    let info = mock_info(TEST_CREATOR, &[]);
+    for listing_id in [first, second].iter() {
+        let res = execute(deps.as_mut(), ended.clone(), info.clone(), close_msg(*listing_id)).unwrap();
+        assert!(res.attributes.contains(&Attribute::new("passed", "true")));
     }
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER));
+    assert_eq!(Uint128::zero(), CONFIG.load(&deps.storage).unwrap().staked_tokens);
+}
 
-    #[test]
-    fn end_listing_zero_quorum() {
-        let mut deps = mock_dependencies(20, &coins(1000, VOTING_TOKEN));
-        mock_init(&mut deps);
-        let mut env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 1000, 10000);
+#[test]
+fn bid_refunds_surplus() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        let msg = create_listing_msg(0, "test".to_string(), None, Some(env.block.height + 1));
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(30u128),
+        refund_surplus: Some(true),
+    };
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![bank_send(TEST_BIDDER, 70)]
+    );
+
+    let bidder_raw = Addr::unchecked(TEST_BIDDER);
+    let token_manager = BANK.load(&deps.storage, &bidder_raw).unwrap();
+    assert_eq!(Uint128::from(30u128), token_manager.token_balance);
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::from(30u128), state.staked_tokens);
+
+    // without the option the surplus stays in the bidder's balance
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(40u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER_2, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.messages.is_empty());
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::from(130u128), state.staked_tokens);
+}
 
-        let handle_res = handle(&mut deps, env.clone(), msg).unwrap();
-        assert_create_listing_result(1, 0, 1001, 0, TEST_CREATOR, handle_res, &mut deps);
-        let msg = HandleMsg::EndListing { listing_id: 1 };
-        env.block.height = &env.block.height + 2;
+#[test]
+fn fails_bid_on_missing_listing() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let handle_res = handle(&mut deps, env.clone(), msg).unwrap();
+    let msg = ExecuteMsg::Bid {
+        listing_id: 0,
+        price: Uint128::from(1u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(1, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing does not exist");
+}
 
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "end_listing"),
-                log("listing_id", "1"),
-                log("rejected_reason", "Quorum not reached"),
-                log("passed", "false"),
-            ]
-        );
-    }
+#[test]
+fn fails_close_missing_listing() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-    #[test]
-    fn end_listing_quorum_rejected() {
-        let mut deps = mock_dependencies(20, &coins(100, VOTING_TOKEN));
-        mock_init(&mut deps);
-        let mut creator_env = mock_env(TEST_CREATOR, &coins(2, VOTING_TOKEN));
+    let msg = ExecuteMsg::CloseBid {
+        listing_id: 7,
+        reserve: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing does not exist");
+}
 
-        let msg = create_listing_msg(
-            30,
-            "test".to_string(),
-            None,
-            Some(creator_env.block.height + 1),
-        );
+#[test]
+fn fails_account_queries_with_invalid_address() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let handle_res = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "create_listing"),
-                log("creator", TEST_CREATOR),
-                log("listing_id", "1"),
-                log("quorum_percentage", "30"),
-                log("end_height", "12346"),
-                log("start_height", "0"),
-            ]
-        );
+    let msg = QueryMsg::Account {
+        address: "a".to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg);
+    assert_generic_err(res, "Invalid input: human address too short");
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let stake_amount = 100;
-        let env = mock_env(TEST_VOTER, &coins(stake_amount, VOTING_TOKEN));
+    let msg = QueryMsg::OffersByOfferer {
+        offerer: "a".to_string(),
+        start_after: None,
+        limit: None,
+    };
+    let res = query(deps.as_ref(), mock_env(), msg);
+    assert_generic_err(res, "Invalid input: human address too short");
+}
 
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_stake_tokens_result(stake_amount, Some(1), handle_res, &mut deps);
+#[test]
+fn fails_token_stake_query_with_invalid_address() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(10u128),
-        };
-        let handle_res = handle(&mut deps, env.clone(), msg).unwrap();
-
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "vote_casted"),
-                log("listing_id", "1"),
-                log("weight", "10"),
-                log("voter", TEST_VOTER),
-            ]
-        );
+    let msg = QueryMsg::TokenStake {
+        address: "a".to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg);
+    assert_generic_err(res, "Invalid input: human address too short");
 
-        let msg = HandleMsg::EndListing { listing_id: 1 };
+    let msg = QueryMsg::TokenStake {
+        address: "some-extremely-long-address-that-is-not-supported-by-the-mock-api".to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg);
+    assert_generic_err(res, "Invalid input: human address too long");
+}
 
-        creator_env.block.height = &creator_env.block.height + 2;
+#[test]
+fn fails_listing_query_on_missing_listing() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    mock_listing(&mut deps, 10);
 
-        let handle_res = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "end_listing"),
-                log("listing_id", "1"),
-                log("rejected_reason", "Quorum not reached"),
-                log("passed", "false"),
-            ]
-        );
-    }
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Listing { listing_id: 99 });
+    assert_generic_err(res, "Listing does not exist");
+}
 
-    #[test]
-    fn end_listing_nay_rejected() {
-        let voter1_stake = 100;
-        let voter2_stake = 1000;
-        let mut deps = mock_dependencies(20, &coins(voter1_stake, VOTING_TOKEN));
-        mock_init(&mut deps);
-        let mut creator_env = mock_env(TEST_CREATOR, &coins(2, VOTING_TOKEN));
+#[test]
+fn fails_close_bid_with_missing_bidder_balance() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 0);
+
+    // inject a bidder that never funded the contract
+    let ghost = Addr::unchecked(TEST_BIDDER_2);
+    let mut a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+    a_listing.bidders.push(ghost);
+    LISTINGS.save(&mut deps.storage, listing_id, &a_listing).unwrap();
+
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Bidder has no staked tokens");
+}
 
-        let msg = create_listing_msg(
-            10,
-            "test".to_string(),
-            None,
-            Some(creator_env.block.height + 1),
-        );
+#[test]
+fn happy_days_deposit() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(11, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    let value: DepositResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(Uint128::from(11u128), value.token_balance);
+    assert_eq!(Uint128::from(11u128), value.available_balance);
+    assert_eq!(Uint128::zero(), value.locked_balance);
+
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::from(11u128), state.staked_tokens);
+
+    // a deposited balance funds a bid without attached coins
+    let listing_id = mock_listing(&mut deps, 5);
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(8u128),
+        refund_surplus: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+
+    let info = mock_info(TEST_BIDDER, &coins(4, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    let value: DepositResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(Uint128::from(15u128), value.token_balance);
+    assert_eq!(Uint128::from(7u128), value.available_balance);
+    assert_eq!(Uint128::from(8u128), value.locked_balance);
+}
 
-        let handle_res = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "create_listing"),
-                log("creator", TEST_CREATOR),
-                log("listing_id", "1"),
-                log("quorum_percentage", "10"),
-                log("end_height", "12346"),
-                log("start_height", "0"),
-            ]
-        );
+#[test]
+fn fails_deposit_unsupported_denom() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(voter1_stake, VOTING_TOKEN));
+    let info = mock_info(TEST_BIDDER, &coins(11, "play money"));
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {});
+    assert_generic_err(res, "Unsupported denom sent: play money");
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(voter1_stake, Some(1), handle_res, &mut deps);
+    let sent = vec![Coin::new(11, TOKEN_DENOM), Coin::new(1, "play money")];
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &sent), ExecuteMsg::Deposit {});
+    assert_generic_err(res, "Unsupported denom sent: play money");
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER_2, &coins(voter2_stake, VOTING_TOKEN));
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), ExecuteMsg::Deposit {});
+    assert_generic_err(res, "Insufficient funds sent");
+}
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(voter1_stake + voter2_stake, Some(1), handle_res, &mut deps);
+#[test]
+fn query_account_reports_locks_and_pending_payouts() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        let env = mock_env(TEST_VOTER_2, &[]);
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "no".to_string(),
-            weight: Uint128::from(voter2_stake),
-        };
-        let handle_res = handle(&mut deps, env, msg).unwrap();
-        assert_cast_vote_success(TEST_VOTER_2, voter2_stake, 1, handle_res);
-
-        let msg = HandleMsg::EndListing { listing_id: 1 };
-
-        creator_env.block.height = &creator_env.block.height + 2;
-        let handle_res = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "end_listing"),
-                log("listing_id", "1"),
-                log("rejected_reason", "Threshold not reached"),
-                log("passed", "false"),
-            ]
-        );
-    }
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(20u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(50u128), value.token_balance);
+    assert_eq!(Uint128::from(30u128), value.available_balance);
+    assert_eq!(vec![(listing_id, Uint128::from(20u128))], value.locked_tokens);
+    assert!(value.unclaimed_listings.is_empty());
+
+    // once the listing has ended the outcome shows up as pending
+    let ended = mock_env_height(mock_env().block.height + DEFAULT_END_HEIGHT, 0);
+    let value = query_account(&deps, TEST_BIDDER, ended.clone());
+    assert_eq!(vec![listing_id], value.unclaimed_listings);
+    assert!(value.pending_proceeds.is_empty());
+
+    let value = query_account(&deps, TEST_CREATOR, ended.clone());
+    assert_eq!(Uint128::zero(), value.token_balance);
+    assert_eq!(
+        vec![(listing_id, Uint128::from(20u128))],
+        value.pending_proceeds
+    );
+
+    // settlement releases the locks but payouts stay pending until claimed
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    execute(deps.as_mut(), ended.clone(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let value = query_account(&deps, TEST_CREATOR, ended.clone());
+    assert_eq!(Uint128::zero(), value.token_balance);
+    assert_eq!(
+        vec![(listing_id, Uint128::from(20u128))],
+        value.pending_proceeds
+    );
+    let value = query_account(&deps, TEST_BIDDER, ended.clone());
+    assert_eq!(Uint128::from(30u128), value.available_balance);
+    assert!(value.locked_tokens.is_empty());
+    assert_eq!(vec![listing_id], value.unclaimed_listings);
+
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    execute(deps.as_mut(), ended.clone(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    execute(deps.as_mut(), ended.clone(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    let value = query_account(&deps, TEST_CREATOR, ended.clone());
+    assert!(value.pending_proceeds.is_empty());
+    let value = query_account(&deps, TEST_BIDDER, ended);
+    assert!(value.unclaimed_listings.is_empty());
+}
 
-    #[test]
-    fn fails_end_listing_before_start_height() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
-
-        let start_height = 1001;
-        let quorum_percentage = 30;
-        let msg = create_listing_msg(
-            quorum_percentage,
-            "test".to_string(),
-            Some(start_height),
-            None,
-        );
+// the (address, listing id) entries of an account index
+fn index_entries(deps: &MockDeps, index: Map<(&Addr, u64), Empty>) -> Vec<(String, u64)> {
+    index
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (address, listing_id) = item.unwrap();
+            (address.to_string(), listing_id)
+        })
+        .collect()
+}
 
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_create_listing_result(
-            1,
-            quorum_percentage,
-            DEFAULT_END_HEIGHT,
-            start_height,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
-        let msg = HandleMsg::EndListing { listing_id: 1 };
+#[test]
+fn account_indexes_follow_the_lead_and_settlement() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let sold = mock_listing(&mut deps, 10);
+    let rejected = mock_listing(&mut deps, 50);
+    assert_eq!(
+        vec![(TEST_CREATOR.to_string(), sold), (TEST_CREATOR.to_string(), rejected)],
+        index_entries(&deps, CREATOR_LISTINGS)
+    );
+
+    // the lead moves with each higher bid
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(sold, 20)).unwrap();
+    let info = mock_info(TEST_BIDDER, &coins(30, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(rejected, 30)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(25, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(sold, 25)).unwrap();
+    assert_eq!(
+        vec![(TEST_BIDDER.to_string(), rejected), (TEST_BIDDER_2.to_string(), sold)],
+        index_entries(&deps, LEADING_BIDS)
+    );
+
+    // a rejected listing leaves both indexes when it is closed
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), ended_env(), info.clone(), close_msg(rejected)).unwrap();
+    execute(deps.as_mut(), ended_env(), info, close_msg(sold)).unwrap();
+    assert_eq!(vec![(TEST_CREATOR.to_string(), sold)], index_entries(&deps, CREATOR_LISTINGS));
+    assert_eq!(vec![(TEST_BIDDER_2.to_string(), sold)], index_entries(&deps, LEADING_BIDS));
+
+    // and a sold one once each side has claimed
+    let msg = ExecuteMsg::ClaimNft { listing_id: sold };
+    execute(deps.as_mut(), ended_env(), mock_info(TEST_BIDDER_2, &[]), msg).unwrap();
+    assert!(index_entries(&deps, LEADING_BIDS).is_empty());
+    let msg = ExecuteMsg::ClaimProceeds { listing_id: sold };
+    execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert!(index_entries(&deps, CREATOR_LISTINGS).is_empty());
+
+    // a delisted fixed-price listing has no proceeds to wait for
+    let listing_id = mock_fixed_price_listing(&mut deps, 40);
+    let msg = ExecuteMsg::Delist { listing_id };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert!(index_entries(&deps, CREATOR_LISTINGS).is_empty());
+}
 
-        let handle_res = handle(&mut deps, env.clone(), msg);
+#[test]
+fn happy_days_claims_after_settlement() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        match handle_res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "Voting period has not started.")
-            }
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
-    }
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(20u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    #[test]
-    fn fails_cast_vote_not_enough_staked() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
-
-        let msg = create_listing_msg(0, "test".to_string(), None, None);
-
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_create_listing_result(
-            1,
-            0,
-            DEFAULT_END_HEIGHT,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert!(res.messages.is_empty());
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::zero(), state.staked_tokens);
+
+    // winner and seller pull their side independently, in any order
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![nft_transfer(TEST_BIDDER, NFT_ID)]
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "NFT already claimed");
+
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![bank_send(TEST_CREATOR, 20)]
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Proceeds already claimed");
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Listing { listing_id }).unwrap();
+    let value: ListingResponse = from_binary(&res).unwrap();
+    assert_eq!(BidStatus::Passed, value.status);
+    assert!(value.nft_claimed);
+    assert!(value.proceeds_claimed);
+}
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(1u128),
-        };
+#[test]
+fn rejected_listing_returns_nft_to_creator() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 50);
 
-        let res = handle(&mut deps, env, msg);
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(20u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "User does not have enough staked tokens.")
-            }
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing is not settled");
+
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg.clone()).unwrap();
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing is not in progress");
+
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone());
+    assert_generic_err(res, "User cannot claim the NFT of this listing.");
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(1, res.messages.len());
+
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing has no proceeds");
+
+    // the losing bid is unlocked and can be withdrawn in full
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![bank_send(TEST_BIDDER, 20)]
+    );
+}
+
+fn make_offer_msg(token_id: &str, price: u128, expires_at_height: Option<u64>) -> ExecuteMsg {
+    ExecuteMsg::MakeOffer {
+        denom: NFT_DENOM.to_string(),
+        token_id: token_id.to_string(),
+        price: Uint128::from(price),
+        expires_at_height,
     }
+}
 
-    #[test]
-    fn happy_days_cast_vote() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+fn query_offers(
+    deps: &MockDeps,
+    msg: QueryMsg,
+) -> Vec<(u64, Uint128)> {
+    let value: OffersResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    value
+        .offers
+        .into_iter()
+        .map(|o| (o.offer_id, o.price))
+        .collect()
+}
 
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
+#[test]
+fn happy_days_accept_offer() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 60, None)).unwrap();
+    let value: CreateOfferResponse = from_binary(&res.data.unwrap()).unwrap();
+    let offer_id = value.offer_id;
+    assert_eq!(1, offer_id);
+
+    // the offered price leaves the balance and is reported as held, the rest stays available
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(40u128), value.token_balance);
+    assert_eq!(Uint128::from(60u128), value.held_balance);
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = ExecuteMsg::AcceptOffer { offer_id };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(res.messages, vec![nft_transfer_from(TEST_CREATOR, TEST_BIDDER, NFT_ID)]);
+
+    let value = query_account(&deps, TEST_CREATOR, mock_env());
+    assert_eq!(Uint128::from(60u128), value.token_balance);
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::from(100u128), state.staked_tokens);
+
+    let offers = query_offers(
+        &deps,
+        QueryMsg::OffersByOfferer {
+            offerer: TEST_BIDDER.to_string(),
+            start_after: None,
+            limit: None,
+        },
+    );
+    assert!(offers.is_empty());
+}
 
-        let quorum_percentage = 30;
+#[test]
+fn fails_accept_offer_with_wrong_nft_or_expired() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let height = mock_env().block.height;
+    let info = mock_info(TEST_BIDDER, &coins(60, TOKEN_DENOM));
+    let msg = make_offer_msg(NFT_ID, 60, Some(height + 10));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+
+    // an NFT held for a listing cannot be sold to an offerer as well
+    mock_listing(&mut deps, 10);
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone());
+    assert_generic_err(res, "NFT is already in escrow");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env_height(height + 10, 0), info, msg);
+    assert_generic_err(res, "Offer has expired");
+
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 2 };
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Offer does not exist");
+}
 
-        let msg = create_listing_msg(quorum_percentage, "test".to_string(), None, None);
+#[test]
+fn fails_make_offer_beyond_available_balance() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_create_listing_result(
-            1,
-            quorum_percentage,
-            DEFAULT_END_HEIGHT,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(30u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 21, None));
+    assert_generic_err(res, "User does not have enough staked tokens.");
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(11, Some(1), handle_res, &mut deps);
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 0, None));
+    assert_generic_err(res, "Offer price must be positive");
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-        let weight = 10u128;
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(weight),
-        };
+    let info = mock_info(TEST_BIDDER, &[]);
+    let msg = make_offer_msg(NFT_ID, 20, Some(mock_env().block.height));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Offer cannot expire in the past");
+
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 20, None)).unwrap();
+}
+
+#[test]
+fn cancel_offer_and_query_offers() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 30, None)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 40, None)).unwrap();
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg("another", 50, None)).unwrap();
+
+    let by_nft = QueryMsg::OffersByNft {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        start_after: None,
+        limit: None,
+    };
+    assert_eq!(
+        vec![(1, Uint128::from(30u128)), (2, Uint128::from(40u128))],
+        query_offers(&deps, by_nft.clone())
+    );
+    let by_offerer = QueryMsg::OffersByOfferer {
+        offerer: TEST_BIDDER.to_string(),
+        start_after: None,
+        limit: None,
+    };
+    assert_eq!(
+        vec![(1, Uint128::from(30u128)), (3, Uint128::from(50u128))],
+        query_offers(&deps, by_offerer.clone())
+    );
+
+    let msg = ExecuteMsg::CancelOffer { offer_id: 1 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER_2, &[]), msg.clone());
+    assert_generic_err(res, "User is not the offerer.");
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone()).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "Offer does not exist");
+
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(50u128), value.token_balance);
+    assert_eq!(
+        vec![(2, Uint128::from(40u128))],
+        query_offers(&deps, by_nft)
+    );
+    assert_eq!(
+        vec![(3, Uint128::from(50u128))],
+        query_offers(&deps, by_offerer)
+    );
+}
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_cast_vote_success(TEST_VOTER, weight, 1, handle_res);
+fn collection_offer_msg(price: u128, quantity: u64, expires_at_height: Option<u64>) -> ExecuteMsg {
+    ExecuteMsg::MakeCollectionOffer {
+        denom: NFT_DENOM.to_string(),
+        price: Uint128::from(price),
+        quantity,
+        expires_at_height,
     }
+}
 
-    #[test]
-    fn happy_days_withdraw_voting_tokens() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+fn fill_collection_offer_msg(offer_id: u64, ids: &[&str]) -> ExecuteMsg {
+    ExecuteMsg::FillCollectionOffer {
+        offer_id,
+        token_ids: ids.iter().map(|id| id.to_string()).collect(),
+    }
+}
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(11, None, handle_res, &mut deps);
-
-        let state = config_read(&mut deps.storage).load().unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: deps
-                    .api
-                    .canonical_address(&HumanAddr::from(TEST_CREATOR))
-                    .unwrap(),
-                listing_count: 0,
-                staked_tokens: Uint128::from(11u128),
-            }
-        );
+fn query_collection_offers(
+    deps: &MockDeps,
+    denom: &str,
+) -> Vec<(u64, Uint128, u64)> {
+    query_collection_offers_page(deps, denom, None, None)
+}
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-        let msg = HandleMsg::WithdrawVotingTokens {
-            amount: Some(Uint128::from(11u128)),
-        };
+fn query_collection_offers_page(
+    deps: &MockDeps,
+    denom: &str,
+    start_after: Option<(u128, u64)>,
+    limit: Option<u32>,
+) -> Vec<(u64, Uint128, u64)> {
+    let msg = QueryMsg::CollectionOffers {
+        denom: denom.to_string(),
+        start_after: start_after.map(|(price, offer_id)| (Uint128::from(price), offer_id)),
+        limit,
+    };
+    let value: CollectionOffersResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    value
+        .offers
+        .into_iter()
+        .map(|o| (o.offer_id, o.price, o.quantity))
+        .collect()
+}
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        let msg = handle_res.messages.get(0).expect("no message");
+#[test]
+fn happy_days_fill_collection_offer() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    // three tokens at 20 each escrows 60
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 3, None)).unwrap();
+    let value: CreateOfferResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(1, value.offer_id);
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(40u128), value.token_balance);
+    assert_eq!(Uint128::from(60u128), value.held_balance);
+
+    // a partial fill with two NFTs
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = fill_collection_offer_msg(1, &[NFT_ID, "second"]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(2, res.messages.len());
+    assert_eq!(res.messages[1], nft_transfer_from(TEST_CREATOR, TEST_BIDDER, "second"));
+    let value = query_account(&deps, TEST_CREATOR, mock_env());
+    assert_eq!(Uint128::from(40u128), value.token_balance);
+    assert_eq!(
+        vec![(1, Uint128::from(20u128), 1)],
+        query_collection_offers(&deps, NFT_DENOM)
+    );
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(20u128), value.held_balance);
+
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    let msg = fill_collection_offer_msg(1, &["third", "fourth"]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Sent more NFTs than the offer quantity");
+
+    // the last unit closes the offer
+    set_nft_owner("third", TEST_BIDDER_2);
+    approve_nft("third");
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &["third"])).unwrap();
+    assert!(query_collection_offers(&deps, NFT_DENOM).is_empty());
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &["fourth"]));
+    assert_generic_err(res, "Offer does not exist");
+
+    let state = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(Uint128::from(100u128), state.staked_tokens);
+}
 
-        assert_eq!(
-            msg,
-            &CosmosMsg::Bank(BankMsg::Send {
-                from_address: HumanAddr::from("cosmos2contract"),
-                to_address: HumanAddr::from(TEST_VOTER),
-                amount: coins(11, VOTING_TOKEN),
-            })
-        );
+#[test]
+fn fails_fill_collection_offer() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let height = mock_env().block.height;
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 0, None));
+    assert_generic_err(res, "Offer quantity must be positive");
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 6, None));
+    assert_generic_err(res, "User does not have enough staked tokens.");
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let msg = collection_offer_msg(20, 5, Some(height + 10));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &[]));
+    assert_generic_err(res, "No NFT sent to fill the offer");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = fill_collection_offer_msg(1, &[NFT_ID, NFT_ID]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Sent the same NFT twice");
+
+    mock_listing(&mut deps, 10);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &[NFT_ID]));
+    assert_generic_err(res, "NFT is already in escrow");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = fill_collection_offer_msg(1, &["second"]);
+    let res = execute(deps.as_mut(), mock_env_height(height + 10, 0), info, msg);
+    assert_generic_err(res, "Offer has expired");
+
+    // an expired offer can still be cancelled to release the escrow
+    let msg = ExecuteMsg::CancelCollectionOffer { offer_id: 1 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone());
+    assert_generic_err(res, "User is not the offerer.");
+    let env = mock_env_height(height + 10, 0);
+    execute(deps.as_mut(), env, mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(100u128), value.token_balance);
+}
 
-        let state = config_read(&mut deps.storage).load().unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: deps
-                    .api
-                    .canonical_address(&HumanAddr::from(TEST_CREATOR))
-                    .unwrap(),
-                listing_count: 0,
-                staked_tokens: Uint128::zero(),
-            }
-        );
-    }
+#[test]
+fn collection_offers_sorted_by_price() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(10, 2, None)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(30, 1, None)).unwrap();
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(10, 1, None)).unwrap();
+    let info = mock_info(TEST_BIDDER, &[]);
+    let msg = ExecuteMsg::MakeCollectionOffer {
+        denom: "othernft".to_string(),
+        price: Uint128::from(50u128),
+        quantity: 1,
+        expires_at_height: None,
+    };
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    assert_eq!(
+        vec![
+            (2, Uint128::from(30u128), 1),
+            (1, Uint128::from(10u128), 2),
+            (3, Uint128::from(10u128), 1),
+        ],
+        query_collection_offers(&deps, NFT_DENOM)
+    );
+    assert_eq!(
+        vec![(4, Uint128::from(50u128), 1)],
+        query_collection_offers(&deps, "othernft")
+    );
+}
 
-    #[test]
-    fn fails_withdraw_voting_tokens_no_stake() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+#[test]
+fn offer_queries_page() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    // offers 1..=12 on NFT_ID, the odd ones by TEST_BIDDER
+    let info = mock_info(TEST_BIDDER, &coins(200, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+    for offer_id in 1..=12u128 {
+        let offerer = if offer_id % 2 == 1 { TEST_BIDDER } else { TEST_BIDDER_2 };
+        let msg = make_offer_msg(NFT_ID, offer_id, None);
+        execute(deps.as_mut(), mock_env(), mock_info(offerer, &[]), msg).unwrap();
+    }
+    let by_nft = |deps: &MockDeps, start_after: Option<u64>, limit: Option<u32>| -> Vec<u64> {
+        let msg = QueryMsg::OffersByNft {
+            denom: NFT_DENOM.to_string(),
+            token_id: NFT_ID.to_string(),
+            start_after,
+            limit,
+        };
+        query_offers(deps, msg).iter().map(|(offer_id, _)| *offer_id).collect()
+    };
+    assert_eq!((1..=10).collect::<Vec<u64>>(), by_nft(&deps, None, None));
+    assert_eq!(vec![11, 12], by_nft(&deps, Some(10), None));
+    assert_eq!(vec![4, 5], by_nft(&deps, Some(3), Some(2)));
+    assert_eq!((1..=12).collect::<Vec<u64>>(), by_nft(&deps, None, Some(100)));
+
+    let msg = QueryMsg::OffersByOfferer {
+        offerer: TEST_BIDDER.to_string(),
+        start_after: Some(5),
+        limit: Some(2),
+    };
+    assert_eq!(
+        vec![(7, Uint128::from(7u128)), (9, Uint128::from(9u128))],
+        query_offers(&deps, msg)
+    );
+
+    // a page of collection offers resumes after the (price, id) it ended on,
+    // also between offers at one price
+    for price in [10, 30, 10, 20, 10].iter() {
+        let msg = collection_offer_msg(*price, 1, None);
+        execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    }
+    let offer_ids = |deps: &MockDeps, start_after: Option<(u128, u64)>, limit: Option<u32>| -> Vec<u64> {
+        query_collection_offers_page(deps, NFT_DENOM, start_after, limit)
+            .iter()
+            .map(|(offer_id, _, _)| *offer_id)
+            .collect()
+    };
+    assert_eq!(vec![2, 4, 1, 3, 5], offer_ids(&deps, None, None));
+    assert_eq!(vec![2, 4], offer_ids(&deps, None, Some(2)));
+    assert_eq!(vec![1, 3], offer_ids(&deps, Some((20, 4)), Some(2)));
+    assert_eq!(vec![5], offer_ids(&deps, Some((10, 3)), Some(2)));
+
+    // filled and cancelled offers leave the indexes
+    let msg = ExecuteMsg::CancelCollectionOffer { offer_id: 4 };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    let msg = fill_collection_offer_msg(2, &[NFT_ID]);
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(vec![1, 3, 5], offer_ids(&deps, None, None));
+    let msg = ExecuteMsg::CancelOffer { offer_id: 3 };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(vec![2, 4], by_nft(&deps, Some(1), Some(2)));
+}
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-        let msg = HandleMsg::WithdrawVotingTokens {
-            amount: Some(Uint128::from(11u128)),
+#[test]
+fn collection_offers_by_offerer_query() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(10, 2, None)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(30, 1, None)).unwrap();
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 3, None)).unwrap();
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 5, None)).unwrap();
+
+    let by_offerer = |deps: &MockDeps, start_after: Option<u64>, limit: Option<u32>| {
+        let msg = QueryMsg::CollectionOffersByOfferer {
+            offerer: TEST_BIDDER.to_string(),
+            start_after,
+            limit,
         };
+        let value: CollectionOffersResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        value
+            .offers
+            .into_iter()
+            .map(|o| (o.offer_id, o.price, o.quantity))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![(1, Uint128::from(10u128), 2), (3, Uint128::from(20u128), 3)],
+        by_offerer(&deps, None, None)
+    );
+    assert_eq!(vec![(3, Uint128::from(20u128), 3)], by_offerer(&deps, Some(1), Some(1)));
+
+    // what the account holds for them adds up the offers of both kinds
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(15u128), value.token_balance);
+    assert_eq!(Uint128::from(85u128), value.held_balance);
+
+    let msg = ExecuteMsg::CancelCollectionOffer { offer_id: 1 };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
+    assert_eq!(vec![(3, Uint128::from(20u128), 3)], by_offerer(&deps, None, None));
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(35u128), value.token_balance);
+    assert_eq!(Uint128::from(65u128), value.held_balance);
+
+    let msg = QueryMsg::CollectionOffersByOfferer {
+        offerer: "a".to_string(),
+        start_after: None,
+        limit: None,
+    };
+    let res = query(deps.as_ref(), mock_env(), msg);
+    assert_generic_err(res, "Invalid input: human address too short");
+}
 
-        let res = handle(&mut deps, env, msg);
+// lists the default NFT at a fixed price as TEST_CREATOR and returns the listing id
+fn mock_fixed_price_listing(
+    deps: &mut MockDeps,
+    price: u128,
+) -> u64 {
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = ExecuteMsg::ListFixedPrice {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        price: Uint128::from(price),
+        description: "test".to_string(),
+        metadata: None,
+    };
+    let msg = with_next_nft(deps, msg);
+    execute(deps.as_mut(), mock_env(), info, msg).expect("contract successfully handles ListFixedPrice");
+    CONFIG.load(&deps.storage).unwrap().listing_count
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Nothing staked"),
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+fn buy_msg(listing_id: u64, price: u128) -> ExecuteMsg {
+    ExecuteMsg::Buy {
+        listing_id,
+        price: Uint128::from(price),
     }
+}
 
-    #[test]
-    fn fails_withdraw_too_many_tokens() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+#[test]
+fn happy_days_buy_fixed_price() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_fixed_price_listing(&mut deps, 50);
 
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(10, VOTING_TOKEN));
+    let msg = ExecuteMsg::UpdatePrice {
+        listing_id,
+        price: Uint128::from(40u128),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+
+    // anything sent above the price stays as balance
+    let info = mock_info(TEST_BIDDER, &coins(60, TOKEN_DENOM));
+    let msg = buy_msg(listing_id, 40);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![nft_transfer(TEST_BIDDER, NFT_ID)]
+    );
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(20u128), value.token_balance);
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Listing { listing_id }).unwrap();
+    let value: ListingResponse = from_binary(&res).unwrap();
+    assert_eq!(ListingKind::FixedPrice, value.kind);
+    assert_eq!(BidStatus::Passed, value.status);
+    assert_eq!(TEST_BIDDER.to_string(), value.highest_bidder);
+    assert!(value.nft_claimed);
+
+    // only the first buyer gets it
+    let info = mock_info(TEST_BIDDER_2, &coins(60, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing is not in progress");
+
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![bank_send(TEST_CREATOR, 40)]
+    );
+}
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(10, None, handle_res, &mut deps);
+#[test]
+fn fails_buy_after_price_update() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_fixed_price_listing(&mut deps, 50);
 
-        let env = mock_env(TEST_VOTER, &[]);
-        let msg = HandleMsg::WithdrawVotingTokens {
-            amount: Some(Uint128::from(11u128)),
-        };
+    // the seller raises the price before the buyer's transaction lands
+    let msg = ExecuteMsg::UpdatePrice {
+        listing_id,
+        price: Uint128::from(80u128),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, buy_msg(listing_id, 50));
+    assert_generic_err(res, "Listing price has changed");
+
+    // nor does a buyer naming a stale price get a cut, and nothing is taken
+    let msg = ExecuteMsg::UpdatePrice {
+        listing_id,
+        price: Uint128::from(40u128),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, buy_msg(listing_id, 50));
+    assert_generic_err(res, "Listing price has changed");
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER));
+    let value = query_listing(&deps, listing_id);
+    assert_eq!(BidStatus::InProgress, value.status);
+
+    // the current price buys it
+    let info = mock_info(TEST_BIDDER, &coins(100, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, buy_msg(listing_id, 40)).unwrap();
+    assert_eq!(Uint128::from(60u128), token_balance(&deps, TEST_BIDDER));
+}
 
-        let res = handle(&mut deps, env, msg);
+#[test]
+fn fails_fixed_price_actions() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let auction_id = mock_listing(&mut deps, 10);
+    let listing_id = mock_fixed_price_listing(&mut deps, 50);
+
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(50u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing is not an auction");
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing is not an auction");
+
+    let msg = buy_msg(auction_id, 10);
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing is not for sale at a fixed price");
+
+    let msg = buy_msg(listing_id, 50);
+    let info = mock_info(TEST_CREATOR, &coins(50, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    assert_generic_err(res, "Creator cannot buy their own listing");
+    let info = mock_info(TEST_BIDDER, &coins(49, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "User does not have enough staked tokens.");
+
+    let msg = ExecuteMsg::UpdatePrice {
+        listing_id,
+        price: Uint128::from(40u128),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg);
+    assert_generic_err(res, "User is not the creator of the listing.");
+    let msg = ExecuteMsg::UpdatePrice {
+        listing_id,
+        price: Uint128::zero(),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Price must be positive");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = ExecuteMsg::ListFixedPrice {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        price: Uint128::zero(),
+        description: "test".to_string(),
+        metadata: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Price must be positive");
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "User is trying to withdraw too many tokens.")
-            }
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+#[test]
+fn delist_returns_nft() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_fixed_price_listing(&mut deps, 50);
+
+    let msg = ExecuteMsg::Delist { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone());
+    assert_generic_err(res, "User is not the creator of the listing.");
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![nft_transfer(TEST_CREATOR, NFT_ID)]
+    );
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing is not in progress");
+    let msg = buy_msg(listing_id, 50);
+    let info = mock_info(TEST_BIDDER, &coins(50, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing is not in progress");
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "NFT already claimed");
+
+    // once it has left the contract the NFT can be deposited and listed again
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), mock_env(), info, list_msg(10, None)).unwrap();
+}
+
+#[test]
+fn query_listings_filters_by_kind() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    mock_listing(&mut deps, 10);
+    mock_fixed_price_listing(&mut deps, 50);
+    mock_listing(&mut deps, 20);
+
+    let listing_ids = |kind: Option<ListingKind>| -> Vec<u64> {
+        query_listing_ids(&deps, kind, None, None)
+    };
+    assert_eq!(vec![1, 2, 3], listing_ids(None));
+    assert_eq!(vec![1, 3], listing_ids(Some(ListingKind::Auction)));
+    assert_eq!(vec![2], listing_ids(Some(ListingKind::FixedPrice)));
+}
+
+fn query_listing_ids(
+    deps: &MockDeps,
+    kind: Option<ListingKind>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Vec<u64> {
+    let msg = QueryMsg::Listings {
+        kind,
+        start_after,
+        limit,
+    };
+    let value: ListingsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    value.listings.iter().map(|l| l.listing_id).collect()
+}
+
+#[test]
+fn query_listings_pages() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    for _ in 0..35 {
+        mock_listing(&mut deps, 10);
     }
+    mock_fixed_price_listing(&mut deps, 50);
+
+    // the first page has the default size; asking for more than the cap gets the cap
+    assert_eq!((1..=10).collect::<Vec<u64>>(), query_listing_ids(&deps, None, None, None));
+    assert_eq!((1..=30).collect::<Vec<u64>>(), query_listing_ids(&deps, None, None, Some(100)));
+
+    // each page starts after the last id of the one before
+    assert_eq!(vec![11, 12, 13], query_listing_ids(&deps, None, Some(10), Some(3)));
+    assert_eq!(
+        vec![34, 35, 36],
+        query_listing_ids(&deps, None, Some(33), Some(30))
+    );
+    assert!(query_listing_ids(&deps, None, Some(36), None).is_empty());
+
+    // a page of one kind skips listings of the other
+    assert_eq!(vec![36], query_listing_ids(&deps, Some(ListingKind::FixedPrice), None, None));
+    assert_eq!(
+        vec![34, 35],
+        query_listing_ids(&deps, Some(ListingKind::Auction), Some(33), None)
+    );
+}
 
-    #[test]
-    fn fails_cast_vote_twice() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+const RESERVE_SALT: &str = "salt";
 
-        let env = mock_env_height(TEST_CREATOR, &coins(2, VOTING_TOKEN), 0, 10000);
+fn reserve_reveal(price: u128) -> ReserveReveal {
+    ReserveReveal {
+        price: Uint128::from(price),
+        salt: RESERVE_SALT.to_string(),
+    }
+}
 
-        let quorum_percentage = 30;
-        let msg = create_listing_msg(quorum_percentage, "test".to_string(), None, None);
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
+// lists the default NFT with a hidden reserve and bids `bid` on it
+fn mock_reserve_listing(
+    deps: &mut MockDeps,
+    reserve: u128,
+    bid: u128,
+) -> u64 {
+    let info = mock_info(TEST_CREATOR, &[]);
+    let mut msg = list_msg(10, None);
+    if let ExecuteMsg::List {
+        reserve_commitment, ..
+    } = &mut msg
+    {
+        *reserve_commitment = Some(reserve_hash(Uint128::from(reserve), RESERVE_SALT));
+    }
+    let msg = with_next_nft(deps, msg);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let listing_id = CONFIG.load(&deps.storage).unwrap().listing_count;
+
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(bid),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(bid, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    listing_id
+}
 
-        assert_create_listing_result(
-            1,
-            quorum_percentage,
-            DEFAULT_END_HEIGHT,
-            0,
-            TEST_CREATOR,
-            handle_res,
-            &mut deps,
-        );
+fn query_listing(
+    deps: &MockDeps,
+    listing_id: u64,
+) -> ListingResponse {
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Listing { listing_id }).unwrap();
+    from_binary(&res).unwrap()
+}
+
+#[test]
+fn hidden_reserve_met_at_settlement() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_reserve_listing(&mut deps, 40, 50);
+    assert!(!query_listing(&deps, listing_id).reserve_met);
+
+    // a reveal that does not hash to the commitment is refused
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: Some(reserve_reveal(30)),
+    };
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Reserve does not match the commitment");
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-        let msg = HandleMsg::StakeVotingTokens {};
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: Some(reserve_reveal(40)),
+    };
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(
+        res.attributes[3],
+        Attribute { key: "passed".to_string(), value: "true".to_string() }
+    );
+    let value = query_listing(&deps, listing_id);
+    assert!(value.reserve_met);
+    assert_eq!(BidStatus::Passed, value.status);
+}
 
-        let handle_res = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-        assert_stake_tokens_result(11, Some(1), handle_res, &mut deps);
+#[test]
+fn hidden_reserve_not_met_or_not_revealed() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        let weight = 1u128;
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(weight),
-        };
-        let handle_res = handle(&mut deps, env.clone(), msg).unwrap();
-        assert_cast_vote_success(TEST_VOTER, weight, 1, handle_res);
+    let listing_id = mock_reserve_listing(&mut deps, 60, 50);
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: Some(reserve_reveal(60)),
+    };
+    let res = execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(
+        res.attributes[2],
+        Attribute {
+            key: "rejected_reason".to_string(),
+            value: "Reserve price not met".to_string(),
+        }
+    );
+    let value = query_listing(&deps, listing_id);
+    assert!(!value.reserve_met);
+    assert_eq!(BidStatus::Rejected, value.status);
+    assert_eq!(TEST_CREATOR.to_string(), value.highest_bidder);
+
+    // closing without a reveal rejects the listing and frees the bid
+    let listing_id = mock_reserve_listing(&mut deps, 10, 50);
+    let msg = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    execute(deps.as_mut(), ended_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(BidStatus::Rejected, query_listing(&deps, listing_id).status);
+    let value = query_account(&deps, TEST_BIDDER, mock_env());
+    assert_eq!(Uint128::from(100u128), value.available_balance);
+}
+
+#[test]
+fn time_based_listing_window() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let now = mock_env().block.time.seconds();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = list_msg(10, Some(Expiration::AtTime(now)));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_generic_err(res, "Listing cannot end in the past");
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = list_msg(10, Some(Expiration::AtTime(now + 7_200)));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // the window closes on block time whatever the height
+    let msg = ExecuteMsg::Bid {
+        listing_id: 1,
+        price: Uint128::from(20u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    let env = mock_env_height(mock_env().block.height + DEFAULT_END_HEIGHT, now + 7_199);
+    execute(deps.as_mut(), env, info, msg.clone()).unwrap();
+
+    let info = mock_info(TEST_BIDDER_2, &coins(30, TOKEN_DENOM));
+    let msg = ExecuteMsg::Bid {
+        listing_id: 1,
+        price: Uint128::from(30u128),
+        refund_surplus: None,
+    };
+    let res = execute(deps.as_mut(), mock_env_height(0, now + 7_200), info, msg);
+    assert_generic_err(res, "Listing has ended");
 
-        let msg = HandleMsg::CastVote {
-            listing_id: 1,
-            vote: "yes".to_string(),
-            weight: Uint128::from(weight),
+    let value = query_account(&deps, TEST_BIDDER, mock_env_height(0, now + 7_200));
+    assert_eq!(vec![1], value.unclaimed_listings);
+}
+
+#[test]
+fn default_end_follows_start_unit() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+
+    let starts = vec![
+        (None, Expiration::AtHeight(env.block.height + DEFAULT_END_HEIGHT)),
+        (
+            Some(Expiration::AtHeight(env.block.height + 10)),
+            Expiration::AtHeight(env.block.height + 10 + DEFAULT_END_HEIGHT),
+        ),
+        (
+            Some(Expiration::AtTime(env.block.time.seconds() + 10)),
+            Expiration::AtTime(env.block.time.seconds() + 10 + 604_800),
+        ),
+    ];
+    for (i, (start, expected)) in starts.into_iter().enumerate() {
+        let info = mock_info(TEST_CREATOR, &[]);
+        let msg = ExecuteMsg::List {
+            denom: NFT_DENOM.to_string(),
+            token_id: format!("token-{}", i),
+            minimum_bid: Uint128::from(10u128),
+            start,
+            end: None,
+            description: "test".to_string(),
+            metadata: None,
+            reserve_commitment: None,
         };
-        let res = handle(&mut deps, env.clone(), msg);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let listing_id = res.attributes[1].value.parse().unwrap();
+        let value = query_listing(&deps, listing_id);
+        assert_eq!(start, value.start);
+        assert_eq!(expected, value.end);
+    }
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "User has already voted."),
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+fn schedule_msg(start: Option<Expiration>, end: Option<Expiration>) -> ExecuteMsg {
+    ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        minimum_bid: Uint128::from(10u128),
+        start,
+        end,
+        description: "test".to_string(),
+        metadata: None,
+        reserve_commitment: None,
     }
+}
 
-    #[test]
-    fn fails_cast_vote_without_listing() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+fn try_schedule(
+    deps: &mut MockDeps,
+    start: Option<Expiration>,
+    end: Option<Expiration>,
+) -> StdResult<Response<NftChainMsg>> {
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = with_next_nft(deps, schedule_msg(start, end));
+    execute(deps.as_mut(), mock_env(), info, msg)
+}
 
-        let msg = HandleMsg::CastVote {
-            listing_id: 0,
-            vote: "yes".to_string(),
-            weight: Uint128::from(1u128),
-        };
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
+#[test]
+fn fails_listing_starting_after_end() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let height = mock_env().block.height;
+
+    let res = try_schedule(
+        &mut deps,
+        Some(Expiration::AtHeight(height + 1_000)),
+        Some(Expiration::AtHeight(height + 1_000)),
+    );
+    assert_generic_err(res, "Listing must start before it ends");
+}
 
-        let res = handle(&mut deps, env, msg);
+#[test]
+fn fails_listing_mixing_height_and_time() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+
+    let res = try_schedule(
+        &mut deps,
+        Some(Expiration::AtHeight(env.block.height + 10)),
+        Some(Expiration::AtTime(env.block.time.seconds() + 7_200)),
+    );
+    assert_generic_err(res, "Listing start and end must use the same unit");
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Listing does not exist"),
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+#[test]
+fn fails_listing_duration_too_short() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+
+    let res = try_schedule(&mut deps, None, Some(Expiration::AtHeight(env.block.height + 599)));
+    assert_generic_err(res, "Listing duration too short");
+    let res = try_schedule(&mut deps, None, Some(Expiration::AtTime(env.block.time.seconds() + 3_599)));
+    assert_generic_err(res, "Listing duration too short");
+
+    // timed from the start, not from now
+    let res = try_schedule(
+        &mut deps,
+        Some(Expiration::AtHeight(env.block.height + 500)),
+        Some(Expiration::AtHeight(env.block.height + 1_000)),
+    );
+    assert_generic_err(res, "Listing duration too short");
+    try_schedule(&mut deps, None, Some(Expiration::AtHeight(env.block.height + 600))).unwrap();
+}
+
+#[test]
+fn fails_listing_duration_too_long() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+
+    let ten_years = Expiration::AtTime(env.block.time.seconds() + 10 * 365 * 86_400);
+    let res = try_schedule(&mut deps, None, Some(ten_years));
+    assert_generic_err(res, "Listing duration too long");
+    let thirty_days = env.block.height + 432_000;
+    let res = try_schedule(&mut deps, None, Some(Expiration::AtHeight(thirty_days + 1)));
+    assert_generic_err(res, "Listing duration too long");
+    try_schedule(&mut deps, None, Some(Expiration::AtHeight(thirty_days))).unwrap();
+}
+
+#[test]
+fn fails_listing_scheduled_too_far_ahead() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let env = mock_env();
+
+    let res = try_schedule(
+        &mut deps,
+        Some(Expiration::AtTime(env.block.time.seconds() + 2_592_001)),
+        None,
+    );
+    assert_generic_err(res, "Listing starts too far in the future");
+    let start = Expiration::AtTime(env.block.time.seconds() + 2_592_000);
+    try_schedule(&mut deps, Some(start), None).unwrap();
+}
+
+#[test]
+fn listing_limits_come_from_config() {
+    let mut deps = mock_deps();
+    let limit = |blocks: u64| Duration { blocks, seconds: blocks * 6 };
+    let msg = InstantiateMsg {
+        denom: String::from(TOKEN_DENOM),
+        min_duration: Some(limit(10)),
+        max_duration: Some(limit(20)),
+        max_start_delay: Some(limit(5)),
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+    let state: State = from_binary(&res).unwrap();
+    assert_eq!(limit(20), state.max_duration);
+
+    let height = mock_env().block.height;
+    let res = try_schedule(&mut deps, None, Some(Expiration::AtHeight(height + 9)));
+    assert_generic_err(res, "Listing duration too short");
+    let res = try_schedule(&mut deps, None, Some(Expiration::AtHeight(height + 21)));
+    assert_generic_err(res, "Listing duration too long");
+    let res = try_schedule(&mut deps, Some(Expiration::AtHeight(height + 6)), None);
+    assert_generic_err(res, "Listing starts too far in the future");
+    try_schedule(
+        &mut deps,
+        Some(Expiration::AtHeight(height + 5)),
+        Some(Expiration::AtHeight(height + 25)),
+    )
+    .unwrap();
+
+    // a fixed-price listing never ends, so the durations do not limit it
+    mock_fixed_price_listing(&mut deps, 10);
+}
+
+#[test]
+fn fails_instantiate_min_duration_above_max() {
+    let limit = |blocks: u64, seconds: u64| Duration { blocks, seconds };
+    for (min, max) in [(limit(21, 60), limit(20, 120)), (limit(10, 121), limit(20, 120))] {
+        let mut deps = mock_deps();
+        let msg = InstantiateMsg {
+            denom: String::from(TOKEN_DENOM),
+            min_duration: Some(min),
+            max_duration: Some(max),
+            max_start_delay: None,
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+        assert_generic_err(res, "Minimum duration exceeds maximum duration");
     }
 
-    #[test]
-    fn happy_days_stake_voting_tokens() {
-        let mut deps = mock_dependencies(20, &[]);
-        mock_init(&mut deps);
+    // the defaults are checked against an explicit limit too
+    let mut deps = mock_deps();
+    let msg = InstantiateMsg {
+        denom: String::from(TOKEN_DENOM),
+        min_duration: None,
+        max_duration: Some(limit(10, 60)),
+        max_start_delay: None,
+    };
+    let res = instantiate(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Minimum duration exceeds maximum duration");
+
+    let mut deps = mock_deps();
+    let msg = InstantiateMsg {
+        denom: String::from(TOKEN_DENOM),
+        min_duration: Some(limit(20, 120)),
+        max_duration: Some(limit(20, 120)),
+        max_start_delay: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+}
+
+// lists the default NFT as TEST_CREATOR opening `delay` blocks from now
+fn mock_scheduled_listing(
+    deps: &mut MockDeps,
+    delay: u64,
+) -> u64 {
+    let start = Expiration::AtHeight(mock_env().block.height + delay);
+    try_schedule(deps, Some(start), None).unwrap();
+    CONFIG.load(&deps.storage).unwrap().listing_count
+}
+
+#[test]
+fn scheduled_listing_opens_at_start() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_scheduled_listing(&mut deps, 100);
+    let height = mock_env().block.height;
+    assert_eq!(BidStatus::Scheduled, query_listing(&deps, listing_id).status);
+
+    let msg = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(20u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    let res = execute(deps.as_mut(), mock_env_height(height + 99, 0), info, msg.clone());
+    assert_generic_err(res, "Listing has not started");
+
+    // the query flips on its own once the start height is reached
+    let opened = mock_env_height(height + 100, 0);
+    let res = query(deps.as_ref(), opened.clone(), QueryMsg::Listing { listing_id }).unwrap();
+    let value: ListingResponse = from_binary(&res).unwrap();
+    assert_eq!(BidStatus::InProgress, value.status);
+
+    let info = mock_info(TEST_BIDDER, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), opened, info, msg).unwrap();
+    let a_listing = LISTINGS.load(&deps.storage, listing_id)
+        .unwrap();
+    assert_eq!(BidStatus::InProgress, a_listing.status);
+}
 
-        let msg = HandleMsg::StakeVotingTokens {};
+#[test]
+fn stats_query_tracks_listings_bids_and_sales() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let passed = mock_listing(&mut deps, 10);
+    let rejected = mock_listing(&mut deps, 10);
+    let scheduled = mock_scheduled_listing(&mut deps, 100);
+    let fixed_price = mock_fixed_price_listing(&mut deps, 7);
+
+    let info = mock_info(TEST_BIDDER, &coins(15, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(passed, 15)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(20, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, bid_msg(passed, 20)).unwrap();
+    let info = mock_info(TEST_CREATOR, &[]);
+    execute(deps.as_mut(), ended_env(), info.clone(), close_msg(passed)).unwrap();
+    execute(deps.as_mut(), ended_env(), info, close_msg(rejected)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(7, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, buy_msg(fixed_price, 7)).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+    let value: StatsResponse = from_binary(&res).unwrap();
+    assert_eq!(4, value.listing_count);
+    assert_eq!(
+        vec![
+            (BidStatus::Scheduled, 1),
+            (BidStatus::InProgress, 0),
+            (BidStatus::Tally, 0),
+            (BidStatus::Passed, 2),
+            (BidStatus::Rejected, 1),
+        ],
+        value.listings_by_status
+    );
+    assert_eq!(coins(27, TOKEN_DENOM), value.volume);
+    assert_eq!(2, value.sales);
+    // only the outbid bidder still has tokens staked
+    assert_eq!(Uint128::from(15u128), value.staked_tokens);
+    assert_eq!(2, value.unique_bidders);
+
+    // past its start the scheduled listing counts as in progress, as the
+    // Listing query reports it, before anyone has bid on it
+    let opened = mock_env_height(mock_env().block.height + 100, 0);
+    let res = query(deps.as_ref(), opened.clone(), QueryMsg::Stats {}).unwrap();
+    let value: StatsResponse = from_binary(&res).unwrap();
+    assert_eq!((BidStatus::Scheduled, 0), value.listings_by_status[0]);
+    assert_eq!((BidStatus::InProgress, 1), value.listings_by_status[1]);
+    let res = query(deps.as_ref(), opened.clone(), QueryMsg::Listing { listing_id: scheduled }).unwrap();
+    let value: ListingResponse = from_binary(&res).unwrap();
+    assert_eq!(BidStatus::InProgress, value.status);
+
+    // the first bid moves it in storage as well, and a repeat bidder is not
+    // counted twice
+    let info = mock_info(TEST_BIDDER, &[]);
+    execute(deps.as_mut(), opened.clone(), info, bid_msg(scheduled, 10)).unwrap();
+    let res = query(deps.as_ref(), opened, QueryMsg::Stats {}).unwrap();
+    let value: StatsResponse = from_binary(&res).unwrap();
+    assert_eq!((BidStatus::Scheduled, 0), value.listings_by_status[0]);
+    assert_eq!((BidStatus::InProgress, 1), value.listings_by_status[1]);
+    assert_eq!(2, value.unique_bidders);
+}
+
+#[test]
+fn stats_query_tracks_offer_sales() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    let info = mock_info(TEST_BIDDER, &coins(30, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 30, None)).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &coins(60, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 3, None)).unwrap();
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let msg = fill_collection_offer_msg(1, &["second", "third"]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+    let value: StatsResponse = from_binary(&res).unwrap();
+    assert_eq!(coins(70, TOKEN_DENOM), value.volume);
+    assert_eq!(3, value.sales);
+    // offers settle outside of any listing
+    assert_eq!(0, value.listing_count);
+    assert!(value.listings_by_status.iter().all(|(_, count)| *count == 0));
+}
 
-        let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
+#[test]
+fn stats_count_status_fails_on_underflow() {
+    let mut stats = Stats::default();
+    stats.count_status(None, &BidStatus::InProgress).unwrap();
+    stats.count_status(Some(&BidStatus::InProgress), &BidStatus::Passed).unwrap();
+
+    let err = stats
+        .count_status(Some(&BidStatus::InProgress), &BidStatus::Rejected)
+        .unwrap_err();
+    assert_eq!(
+        StdError::overflow(OverflowError::new(OverflowOperation::Sub, 0, 1)),
+        err
+    );
+}
 
-        let handle_res = handle(&mut deps, env, msg.clone()).unwrap();
-        assert_stake_tokens_result(11, None, handle_res, &mut deps);
+#[test]
+fn update_listing_before_first_bid() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_scheduled_listing(&mut deps, 100);
+    let height = mock_env().block.height;
+
+    let msg = ExecuteMsg::UpdateListing {
+        listing_id,
+        description: Some("fixed typo".to_string()),
+        minimum_bid: Some(Uint128::from(25u128)),
+        end: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), msg.clone());
+    assert_generic_err(res, "User is not the creator of the listing.");
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res.attributes[2..],
+        [
+            Attribute { key: "description".to_string(), value: "fixed typo".to_string() },
+            Attribute { key: "minimum_bid".to_string(), value: "25".to_string() },
+        ]
+    );
+    let value = query_listing(&deps, listing_id);
+    assert_eq!("fixed typo", value.description);
+    assert_eq!(Uint128::from(25u128), value.minimum_bid);
+
+    let msg = ExecuteMsg::UpdateListing {
+        listing_id,
+        description: Some("no".to_string()),
+        minimum_bid: None,
+        end: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone());
+    assert_generic_err(res, "Description too short");
+
+    // still editable once open, until someone bids
+    let end = Expiration::AtHeight(height + 1_000);
+    let msg = ExecuteMsg::UpdateListing {
+        listing_id,
+        description: None,
+        minimum_bid: None,
+        end: Some(end),
+    };
+    let env = mock_env_height(height + 100, 0);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap();
+    assert_eq!(
+        res.attributes[2],
+        Attribute { key: "end".to_string(), value: "expiration height: 13345".to_string() }
+    );
+    assert_eq!(end, query_listing(&deps, listing_id).end);
+
+    let bid = ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(30u128),
+        refund_surplus: None,
+    };
+    let info = mock_info(TEST_BIDDER, &coins(30, TOKEN_DENOM));
+    execute(deps.as_mut(), env.clone(), info, bid).unwrap();
+    let res = execute(deps.as_mut(), env, mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing cannot be edited after the first bid");
+}
+
+#[test]
+fn fails_update_listing_with_invalid_end() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
+    let height = mock_env().block.height;
+
+    let update = |end: u64| ExecuteMsg::UpdateListing {
+        listing_id,
+        description: None,
+        minimum_bid: None,
+        end: Some(Expiration::AtHeight(end)),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), update(height));
+    assert_generic_err(res, "Listing cannot end in the past");
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), update(height + 10));
+    assert_generic_err(res, "Listing duration too short");
+
+    let fixed_id = mock_fixed_price_listing(&mut deps, 50);
+    let msg = ExecuteMsg::UpdateListing {
+        listing_id: fixed_id,
+        description: Some("fixed typo".to_string()),
+        minimum_bid: None,
+        end: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Listing is not an auction");
+}
+
+#[test]
+fn closing_scheduled_listing_returns_nft() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_scheduled_listing(&mut deps, 100);
+
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg.clone());
+    assert_generic_err(res, "Listing is not settled");
+
+    let close = ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), close).unwrap();
+    assert_eq!(BidStatus::Rejected, query_listing(&deps, listing_id).status);
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+}
+
+fn mock_metadata() -> ListingMetadata {
+    ListingMetadata {
+        title: "ベン #1".to_string(),
+        image: Some("ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG".to_string()),
+        external_url: Some("https://example.com/ben/1".to_string()),
+        attributes: vec![MetadataAttribute {
+            trait_type: "背景".to_string(),
+            value: "青".to_string(),
+        }],
+    }
+}
+
+fn try_list_with_metadata(
+    deps: &mut MockDeps,
+    description: &str,
+    metadata: ListingMetadata,
+) -> StdResult<Response<NftChainMsg>> {
+    let info = mock_info(TEST_CREATOR, &[]);
+    let mut msg = list_msg(10, None);
+    if let ExecuteMsg::List {
+        description: d,
+        metadata: m,
+        ..
+    } = &mut msg
+    {
+        *d = description.to_string();
+        *m = Some(metadata);
     }
+    let msg = with_next_nft(deps, msg);
+    execute(deps.as_mut(), mock_env(), info, msg)
+}
 
-    #[test]
-    fn fails_insufficient_funds() {
-        let mut deps = mock_dependencies(20, &[]);
+#[test]
+fn listing_metadata_is_returned() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
 
-        // initialize the store
-        let msg = init_msg();
-        let env = mock_env(TEST_VOTER, &coins(2, VOTING_TOKEN));
-        let init_res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, init_res.messages.len());
+    // 60 characters but 180 bytes
+    let description = "競".repeat(60);
+    try_list_with_metadata(&mut deps, &description, mock_metadata()).unwrap();
 
-        // insufficient token
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(0, VOTING_TOKEN));
+    let value = query_listing(&deps, 1);
+    assert_eq!(description, value.description);
+    assert_eq!(Some(mock_metadata()), value.metadata);
 
-        let res = handle(&mut deps, env, msg);
+    let res = try_list_with_metadata(&mut deps, &"競".repeat(65), mock_metadata());
+    assert_generic_err(res, "Description too long");
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Insufficient funds sent"),
-            Err(e) => panic!("Unexpected error: {:?}", e),
-        }
+#[test]
+fn fails_list_with_invalid_metadata() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    type Mutation = fn(&mut ListingMetadata);
+    let cases: Vec<(Mutation, &str)> = vec![
+        (|m| m.title = String::new(), "Title is empty"),
+        (|m| m.title = "題".repeat(65), "Title too long"),
+        (|m| m.image = Some("not a uri".to_string()), "Image URI is not a valid URI"),
+        (|m| m.image = Some("ipfs:".to_string()), "Image URI is not a valid URI"),
+        (
+            |m| m.image = Some(format!("https://{}", "a".repeat(249))),
+            "Image URI too long",
+        ),
+        (
+            |m| m.external_url = Some("://example.com".to_string()),
+            "External URL is not a valid URI",
+        ),
+        (
+            |m| m.attributes = vec![m.attributes[0].clone(); 17],
+            "Too many attributes",
+        ),
+        (
+            |m| m.attributes[0].trait_type = "種".repeat(33),
+            "Attribute trait type too long",
+        ),
+        (|m| m.attributes[0].value = String::new(), "Attribute value is empty"),
+    ];
+    for (mutate, expected) in cases {
+        let mut metadata = mock_metadata();
+        mutate(&mut metadata);
+        let res = try_list_with_metadata(&mut deps, "test", metadata);
+        assert_generic_err(res, expected);
     }
 
-    #[test]
-    fn fails_staking_wrong_token() {
-        let mut deps = mock_dependencies(20, &[]);
+    // the limits themselves are accepted
+    let mut metadata = mock_metadata();
+    metadata.title = "題".repeat(64);
+    metadata.image = Some(format!("https://{}", "a".repeat(248)));
+    metadata.attributes = vec![metadata.attributes[0].clone(); 16];
+    try_list_with_metadata(&mut deps, "test", metadata).unwrap();
+}
+
+#[test]
+fn listing_snapshots_token_uri() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+    let listing_id = mock_listing(&mut deps, 10);
 
-        // initialize the store
-        let msg = init_msg();
-        let env = mock_env(TEST_VOTER, &coins(2, VOTING_TOKEN));
-        let init_res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, init_res.messages.len());
+    let value = query_listing(&deps, listing_id);
+    assert_eq!(Some(format!("ipfs://{}/{}", NFT_DENOM, NFT_ID)), value.token_uri);
+}
 
-        // wrong token
-        let msg = HandleMsg::StakeVotingTokens {};
-        let env = mock_env(TEST_VOTER, &coins(11, "play money"));
+#[test]
+fn fails_list_or_sell_nft_without_approval() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    // the owner holds the NFT but has not let the contract move it
+    set_nft_owner(NFT_ID, TEST_CREATOR);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None));
+    assert_generic_err(res, "Contract is not approved to transfer the NFT");
+
+    let info = mock_info(TEST_BIDDER, &coins(60, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 30, None)).unwrap();
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER, &[]), collection_offer_msg(30, 1, None))
+        .unwrap();
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg);
+    assert_generic_err(res, "Contract is not approved to transfer the NFT");
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &[NFT_ID]));
+    assert_generic_err(res, "Contract is not approved to transfer the NFT");
+
+    // an approval is given to one operator only
+    NFT_APPROVALS.with(|approvals| {
+        approvals
+            .borrow_mut()
+            .insert((NFT_DENOM.to_string(), NFT_ID.to_string()), TEST_BIDDER_2.to_string())
+    });
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None));
+    assert_generic_err(res, "Contract is not approved to transfer the NFT");
+
+    // once approved, the contract pulls the NFT as operator
+    approve_nft(NFT_ID);
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(res.messages, vec![nft_transfer_from(TEST_CREATOR, TEST_BIDDER, NFT_ID)]);
+}
 
-        let res = handle(&mut deps, env, msg);
+#[test]
+fn fails_list_or_sell_nft_of_someone_else() {
+    let mut deps = mock_deps();
+    mock_init(&mut deps);
+
+    // an NFT someone else handed to the contract is not the sender's to sell
+    set_nft_owner(NFT_ID, MOCK_CONTRACT_ADDR);
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None));
+    assert_generic_err(res, "NFT is not owned by the sender");
+    let msg = ExecuteMsg::ListFixedPrice {
+        denom: NFT_DENOM.to_string(),
+        token_id: NFT_ID.to_string(),
+        price: Uint128::from(10u128),
+        description: "test".to_string(),
+        metadata: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER_2, &[]), msg);
+    assert_generic_err(res, "NFT is not owned by the sender");
+
+    set_nft_owner(NFT_ID, TEST_CREATOR);
+    approve_nft(NFT_ID);
+    let info = mock_info(TEST_BIDDER, &coins(60, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, make_offer_msg(NFT_ID, 60, None)).unwrap();
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(TEST_BIDDER_2, &[]), msg);
+    assert_generic_err(res, "NFT is not owned by the sender");
+
+    let info = mock_info(TEST_BIDDER, &coins(40, TOKEN_DENOM));
+    execute(deps.as_mut(), mock_env(), info, collection_offer_msg(20, 2, None)).unwrap();
+    set_nft_owner("second", TEST_BIDDER_2);
+    approve_nft("second");
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &["second", NFT_ID]));
+    assert_generic_err(res, "NFT is not owned by the sender");
+
+    // nothing moved, and the owner can still take both offers
+    assert_eq!(Uint128::zero(), token_balance(&deps, TEST_BIDDER_2));
+    let msg = ExecuteMsg::AcceptOffer { offer_id: 1 };
+    execute(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    let info = mock_info(TEST_BIDDER_2, &[]);
+    execute(deps.as_mut(), mock_env(), info, fill_collection_offer_msg(1, &["second"])).unwrap();
+
+    // without an NFT module to ask, nothing can be listed
+    let mut deps = OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: MockQuerier::<NftQuery>::new(&[]),
+        custom_query_type: PhantomData,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(TEST_CREATOR, &[]), init_msg()).unwrap();
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, list_msg(10, None));
+    assert_generic_err(
+        res,
+        "Querier system error: Unsupported query type: custom",
+    );
+}
 
-        match res {
-            Ok(_) => panic!("Must return error"),
-            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Insufficient funds sent"),
-            Err(e) => panic!("Unexpected error: {:?}", e),
+const FUZZ_USERS: [&str; 4] = [TEST_CREATOR, TEST_BIDDER, TEST_BIDDER_2, "bidder3"];
+const FUZZ_NFTS: [&str; 3] = [NFT_ID, "second", "third"];
+
+#[derive(Clone, Debug)]
+enum Action {
+    Deposit {
+        sender: usize,
+        sent: u128,
+    },
+    List {
+        creator: usize,
+        nft: usize,
+        minimum_bid: u128,
+        reserve: Option<u128>,
+    },
+    Bid {
+        bidder: usize,
+        listing_id: u64,
+        price: u128,
+        sent: u128,
+        refund_surplus: bool,
+    },
+    CloseBid {
+        sender: usize,
+        listing_id: u64,
+        reserve: Option<u128>,
+    },
+    ListFixedPrice {
+        creator: usize,
+        nft: usize,
+        price: u128,
+    },
+    Buy {
+        sender: usize,
+        listing_id: u64,
+        sent: u128,
+    },
+    UpdatePrice {
+        sender: usize,
+        listing_id: u64,
+        price: u128,
+    },
+    Delist {
+        sender: usize,
+        listing_id: u64,
+    },
+    ClaimNft {
+        sender: usize,
+        listing_id: u64,
+    },
+    ClaimProceeds {
+        sender: usize,
+        listing_id: u64,
+    },
+    MakeOffer {
+        sender: usize,
+        price: u128,
+        sent: u128,
+    },
+    CancelOffer {
+        sender: usize,
+        offer_id: u64,
+    },
+    AcceptOffer {
+        sender: usize,
+        offer_id: u64,
+    },
+    MakeCollectionOffer {
+        sender: usize,
+        price: u128,
+        quantity: u64,
+        sent: u128,
+    },
+    CancelCollectionOffer {
+        sender: usize,
+        offer_id: u64,
+    },
+    FillCollectionOffer {
+        sender: usize,
+        offer_id: u64,
+        filled: usize,
+    },
+    WithdrawTokens {
+        sender: usize,
+        amount: Option<u128>,
+    },
+    // lets listings reach their end, at five seconds a block
+    AdvanceBlocks {
+        blocks: u64,
+    },
+}
+
+fn action_strategy() -> impl Strategy<Value = Action> {
+    let user = 0..FUZZ_USERS.len();
+    let nft = 0..FUZZ_NFTS.len();
+    prop_oneof![
+        (user.clone(), 0..100u128).prop_map(|(sender, sent)| Action::Deposit { sender, sent }),
+        (user.clone(), nft.clone(), 0..50u128, proptest::option::of(0..60u128)).prop_map(
+            |(creator, nft, minimum_bid, reserve)| Action::List {
+                creator,
+                nft,
+                minimum_bid,
+                reserve,
+            }
+        ),
+        (user.clone(), 0..6u64, 0..100u128, 0..100u128, any::<bool>()).prop_map(
+            |(bidder, listing_id, price, sent, refund_surplus)| Action::Bid {
+                bidder,
+                listing_id,
+                price,
+                sent,
+                refund_surplus,
+            }
+        ),
+        (user.clone(), 0..6u64, proptest::option::of(0..60u128)).prop_map(
+            |(sender, listing_id, reserve)| Action::CloseBid {
+                sender,
+                listing_id,
+                reserve,
+            }
+        ),
+        (user.clone(), nft, 0..50u128)
+            .prop_map(|(creator, nft, price)| Action::ListFixedPrice { creator, nft, price }),
+        (user.clone(), 0..6u64, 0..100u128)
+            .prop_map(|(sender, listing_id, sent)| Action::Buy { sender, listing_id, sent }),
+        (user.clone(), 0..6u64, 0..50u128).prop_map(|(sender, listing_id, price)| {
+            Action::UpdatePrice {
+                sender,
+                listing_id,
+                price,
+            }
+        }),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, listing_id)| Action::Delist { sender, listing_id }),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, listing_id)| Action::ClaimNft { sender, listing_id }),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, listing_id)| Action::ClaimProceeds { sender, listing_id }),
+        (user.clone(), 0..100u128, 0..100u128)
+            .prop_map(|(sender, price, sent)| Action::MakeOffer { sender, price, sent }),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, offer_id)| Action::CancelOffer { sender, offer_id }),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, offer_id)| Action::AcceptOffer { sender, offer_id }),
+        (user.clone(), 0..40u128, 0..4u64, 0..100u128).prop_map(
+            |(sender, price, quantity, sent)| Action::MakeCollectionOffer {
+                sender,
+                price,
+                quantity,
+                sent,
+            }
+        ),
+        (user.clone(), 0..6u64)
+            .prop_map(|(sender, offer_id)| Action::CancelCollectionOffer { sender, offer_id }),
+        (user.clone(), 0..6u64, 0..4usize).prop_map(|(sender, offer_id, filled)| {
+            Action::FillCollectionOffer {
+                sender,
+                offer_id,
+                filled,
+            }
+        }),
+        (user, proptest::option::of(0..100u128))
+            .prop_map(|(sender, amount)| Action::WithdrawTokens { sender, amount }),
+        (0..60_000u64).prop_map(|blocks| Action::AdvanceBlocks { blocks }),
+    ]
+}
+
+fn run_action(
+    deps: &mut MockDeps,
+    env: &Env,
+    action: &Action,
+) -> StdResult<Response<NftChainMsg>> {
+    let env = env.clone();
+    match *action {
+        Action::Deposit { sender, sent } => {
+            let info = mock_info(FUZZ_USERS[sender], &coins(sent, TOKEN_DENOM));
+            execute(deps.as_mut(), env, info, ExecuteMsg::Deposit {})
+        }
+        Action::List {
+            creator,
+            nft,
+            minimum_bid,
+            reserve,
+        } => {
+            let info = mock_info(FUZZ_USERS[creator], &[]);
+            let msg = ExecuteMsg::List {
+                denom: NFT_DENOM.to_string(),
+                token_id: FUZZ_NFTS[nft].to_string(),
+                minimum_bid: Uint128::from(minimum_bid),
+                start: None,
+                end: None,
+                description: "test".to_string(),
+                metadata: None,
+                reserve_commitment: reserve.map(|price| {
+                    reserve_hash(Uint128::from(price), RESERVE_SALT)
+                }),
+            };
+            execute(deps.as_mut(), env, info, msg)
+        }
+        Action::Bid {
+            bidder,
+            listing_id,
+            price,
+            sent,
+            refund_surplus,
+        } => {
+            let info = mock_info(FUZZ_USERS[bidder], &coins(sent, TOKEN_DENOM));
+            let msg = ExecuteMsg::Bid {
+                listing_id,
+                price: Uint128::from(price),
+                refund_surplus: Some(refund_surplus),
+            };
+            execute(deps.as_mut(), env, info, msg)
+        }
+        Action::CloseBid {
+            sender,
+            listing_id,
+            reserve,
+        } => {
+            let msg = ExecuteMsg::CloseBid {
+                listing_id,
+                reserve: reserve.map(reserve_reveal),
+            };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::ListFixedPrice { creator, nft, price } => {
+            let info = mock_info(FUZZ_USERS[creator], &[]);
+            let msg = ExecuteMsg::ListFixedPrice {
+                denom: NFT_DENOM.to_string(),
+                token_id: FUZZ_NFTS[nft].to_string(),
+                price: Uint128::from(price),
+                description: "test".to_string(),
+                metadata: None,
+            };
+            execute(deps.as_mut(), env, info, msg)
+        }
+        Action::Buy {
+            sender,
+            listing_id,
+            sent,
+        } => {
+            // at the listed price, so the sale itself is what gets exercised
+            let price = LISTINGS
+                .may_load(&deps.storage, listing_id)
+                .unwrap()
+                .map(|a_listing| a_listing.minimum_bid)
+                .unwrap_or_default();
+            let info = mock_info(FUZZ_USERS[sender], &coins(sent, TOKEN_DENOM));
+            execute(deps.as_mut(), env, info, ExecuteMsg::Buy { listing_id, price })
+        }
+        Action::UpdatePrice {
+            sender,
+            listing_id,
+            price,
+        } => {
+            let msg = ExecuteMsg::UpdatePrice {
+                listing_id,
+                price: Uint128::from(price),
+            };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::Delist { sender, listing_id } => {
+            let msg = ExecuteMsg::Delist { listing_id };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::ClaimNft { sender, listing_id } => {
+            let msg = ExecuteMsg::ClaimNft { listing_id };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::ClaimProceeds { sender, listing_id } => {
+            let msg = ExecuteMsg::ClaimProceeds { listing_id };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::MakeOffer {
+            sender,
+            price,
+            sent,
+        } => {
+            let info = mock_info(FUZZ_USERS[sender], &coins(sent, TOKEN_DENOM));
+            execute(deps.as_mut(), env, info, make_offer_msg(NFT_ID, price, None))
+        }
+        Action::CancelOffer { sender, offer_id } => {
+            let msg = ExecuteMsg::CancelOffer { offer_id };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::AcceptOffer { sender, offer_id } => {
+            let info = mock_info(FUZZ_USERS[sender], &[]);
+            let msg = ExecuteMsg::AcceptOffer { offer_id };
+            execute(deps.as_mut(), env, info, msg)
+        }
+        Action::MakeCollectionOffer {
+            sender,
+            price,
+            quantity,
+            sent,
+        } => {
+            let info = mock_info(FUZZ_USERS[sender], &coins(sent, TOKEN_DENOM));
+            execute(deps.as_mut(), env, info, collection_offer_msg(price, quantity, None))
+        }
+        Action::CancelCollectionOffer { sender, offer_id } => {
+            let msg = ExecuteMsg::CancelCollectionOffer { offer_id };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::FillCollectionOffer {
+            sender,
+            offer_id,
+            filled,
+        } => {
+            // ids no listing uses, so only the offer's own checks apply
+            let msg = ExecuteMsg::FillCollectionOffer {
+                offer_id,
+                token_ids: (0..filled).map(|i| format!("fill-{}", i)).collect(),
+            };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
+        }
+        Action::AdvanceBlocks { .. } => Ok(Response::new()),
+        Action::WithdrawTokens { sender, amount } => {
+            let msg = ExecuteMsg::WithdrawTokens {
+                amount: amount.map(Uint128::from),
+            };
+            execute(deps.as_mut(), env, mock_info(FUZZ_USERS[sender], &[]), msg)
         }
     }
+}
 
-    // helper to confirm the expected create_listing response
-    fn assert_create_listing_result(
-        listing_id: u64,
-        quorum: u8,
-        end_height: u64,
-        start_height: u64,
-        creator: &str,
-        handle_res: HandleResponse,
-        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
-    ) {
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "create_listing"),
-                log("creator", creator),
-                log("listing_id", listing_id.to_string()),
-                log("quorum_percentage", quorum.to_string()),
-                log("end_height", end_height.to_string()),
-                log("start_height", start_height.to_string()),
-            ]
-        );
+fn sent_amount(action: &Action) -> u128 {
+    match *action {
+        Action::Deposit { sent, .. }
+        | Action::Bid { sent, .. }
+        | Action::Buy { sent, .. }
+        | Action::MakeOffer { sent, .. }
+        | Action::MakeCollectionOffer { sent, .. } => sent,
+        _ => 0,
+    }
+}
 
-        //confirm listing count
-        let state = config_read(&deps.storage).load().unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: deps
-                    .api
-                    .canonical_address(&HumanAddr::from(TEST_CREATOR))
-                    .unwrap(),
-                listing_count: 1,
-                staked_tokens: Uint128::zero(),
+// the price and number of NFTs an offer settlement would pay for, read before it runs
+fn offer_sale(deps: &MockDeps, action: &Action) -> (u128, u64) {
+    match *action {
+        Action::AcceptOffer { offer_id, .. } => OFFERS
+            .may_load(&deps.storage, offer_id)
+            .unwrap()
+            .map_or((0, 0), |an_offer| (an_offer.price.u128(), 1)),
+        Action::FillCollectionOffer {
+            offer_id, filled, ..
+        } => COLLECTION_OFFERS
+            .may_load(&deps.storage, offer_id)
+            .unwrap()
+            .map_or((0, 0), |an_offer| (an_offer.price.u128() * filled as u128, filled as u64)),
+        _ => (0, 0),
+    }
+}
+
+fn withdrawn_amount(res: &Response<NftChainMsg>) -> u128 {
+    res.messages
+        .iter()
+        .map(|sub_msg| match &sub_msg.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount
+                .iter()
+                .filter(|coin| coin.denom == TOKEN_DENOM)
+                .map(|coin| coin.amount.u128())
+                .sum(),
+            _ => 0,
+        })
+        .sum()
+}
+
+// the chain moves the NFTs of a successful transaction; a token moved by
+// anyone but its owner or the operator it approved would fail it
+fn apply_nft_transfers(res: &Response<NftChainMsg>) -> Result<(), TestCaseError> {
+    for sub_msg in &res.messages {
+        let (denom, id, recipient) = match &sub_msg.msg {
+            CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::Transfer {
+                sender,
+                recipient,
+                denom,
+                id,
+            })) => {
+                let owner = nft_owner(denom, id).unwrap_or_else(|| TEST_CREATOR.to_string());
+                prop_assert_eq!(sender, &owner, "{}/{} moved by a non-owner", denom, id);
+                (denom, id, recipient)
             }
-        );
+            CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::TransferFrom {
+                sender,
+                owner,
+                recipient,
+                denom,
+                id,
+            })) => {
+                let current = nft_owner(denom, id).unwrap_or_else(|| TEST_CREATOR.to_string());
+                prop_assert_eq!(owner, &current, "{}/{} moved from a non-owner", denom, id);
+                prop_assert!(nft_approved(denom, id, sender), "{}/{} moved without approval", denom, id);
+                (denom, id, recipient)
+            }
+            _ => continue,
+        };
+        NFT_APPROVALS.with(|approvals| approvals.borrow_mut().remove(&(denom.clone(), id.clone())));
+        NFT_OWNERS.with(|owners| {
+            owners.borrow_mut().insert((denom.clone(), id.clone()), recipient.clone())
+        });
     }
+    Ok(())
+}
 
-    fn assert_stake_tokens_result(
-        staked_tokens: u128,
-        listing_count: Option<u64>,
-        handle_res: HandleResponse,
-        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
-    ) {
-        assert_eq!(handle_res, HandleResponse::default());
-
-        let state = config_read(&mut deps.storage).load().unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: deps
-                    .api
-                    .canonical_address(&HumanAddr::from(TEST_CREATOR))
-                    .unwrap(),
-                listing_count: listing_count.unwrap_or_default(),
-                staked_tokens: Uint128::from(staked_tokens),
+// what must hold between any two transactions, given the volume and sales
+// settled through offers so far, which no listing records
+fn check_invariants(
+    deps: &MockDeps,
+    env: &Env,
+    offer_volume: u128,
+    offer_sales: u64,
+) -> Result<(), TestCaseError> {
+    let state = CONFIG.load(&deps.storage).unwrap();
+
+    let mut total_balance = 0u128;
+    let mut total_held = 0u128;
+    for user in FUZZ_USERS.iter() {
+        let token_manager = BANK
+            .may_load(&deps.storage, &Addr::unchecked(*user))
+            .unwrap()
+            .unwrap_or_default();
+        let locked: u128 = token_manager.locked_tokens.iter().map(|(_, amount)| amount.u128()).sum();
+        prop_assert!(locked <= token_manager.token_balance.u128(), "{} locks more than it holds", user);
+        total_balance += token_manager.token_balance.u128();
+
+        // the account query answers from its per-address indexes what a scan of
+        // every listing finds
+        let value = query_account(deps, user, env.clone());
+        total_held += value.held_balance.u128();
+        let mut pending_proceeds = vec![];
+        let mut unclaimed_listings = vec![];
+        for listing_id in 1..=state.listing_count {
+            let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+            let (proceeds_pending, nft_pending) = match a_listing.status {
+                BidStatus::Passed => (!a_listing.proceeds_claimed, !a_listing.nft_claimed),
+                BidStatus::InProgress | BidStatus::Scheduled => {
+                    let won = a_listing.end.is_expired(&env.block)
+                        && a_listing.reserve_commitment.is_none()
+                        && !a_listing.bidders.is_empty()
+                        && a_listing.highest_bid >= a_listing.minimum_bid;
+                    (won, won)
+                }
+                _ => (false, false),
+            };
+            if proceeds_pending && a_listing.creator == *user {
+                pending_proceeds.push((listing_id, a_listing.highest_bid));
             }
+            if nft_pending && a_listing.highest_bidder == *user {
+                unclaimed_listings.push(listing_id);
+            }
+        }
+        prop_assert_eq!(pending_proceeds, value.pending_proceeds, "{} pending proceeds", user);
+        prop_assert_eq!(unclaimed_listings, value.unclaimed_listings, "{} unclaimed listings", user);
+    }
+    prop_assert_eq!(total_balance, state.staked_tokens.u128());
+
+    // the accounts report as held exactly what the open offers take out of balances
+    let open_offers: u128 = OFFERS
+        .range(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap().1.price.u128())
+        .sum::<u128>()
+        + COLLECTION_OFFERS
+            .range(&deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let an_offer = item.unwrap().1;
+                an_offer.price.u128() * u128::from(an_offer.quantity)
+            })
+            .sum::<u128>();
+    prop_assert_eq!(open_offers, total_held);
+
+    // every open offer is indexed, and every index entry is an open offer
+    let offers: Vec<_> = OFFERS
+        .range(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap())
+        .collect();
+    let by_nft: Vec<_> = OFFERS_BY_NFT
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap())
+        .collect();
+    let mut by_offerer: Vec<_> = OFFERS_BY_OFFERER
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap())
+        .collect();
+    by_offerer.sort_by_key(|(_, offer_id)| *offer_id);
+    let mut expected_by_nft: Vec<_> = offers
+        .iter()
+        .map(|(offer_id, o)| (o.denom.clone(), o.token_id.clone(), *offer_id))
+        .collect();
+    expected_by_nft.sort();
+    prop_assert_eq!(expected_by_nft, by_nft);
+    let expected_by_offerer: Vec<_> =
+        offers.iter().map(|(offer_id, o)| (o.offerer.clone(), *offer_id)).collect();
+    prop_assert_eq!(expected_by_offerer, by_offerer);
+    let mut by_price: Vec<_> = COLLECTION_OFFERS_BY_PRICE
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap())
+        .collect();
+    by_price.sort_by_key(|(_, _, offer_id)| *offer_id);
+    let expected_by_price: Vec<_> = COLLECTION_OFFERS
+        .range(&deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (offer_id, o) = item.unwrap();
+            (o.denom, u128::MAX - o.price.u128(), offer_id)
+        })
+        .collect();
+    prop_assert_eq!(expected_by_price, by_price);
+    let mut by_offerer: Vec<_> = COLLECTION_OFFERS_BY_OFFERER
+        .keys(&deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap())
+        .collect();
+    by_offerer.sort_by_key(|(_, offer_id)| *offer_id);
+    let expected_by_offerer: Vec<_> = COLLECTION_OFFERS
+        .range(&deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (offer_id, o) = item.unwrap();
+            (o.offerer, offer_id)
+        })
+        .collect();
+    prop_assert_eq!(expected_by_offerer, by_offerer);
+
+    // the indexes drop a listing once there is nothing left to claim from it
+    for item in CREATOR_LISTINGS.keys(&deps.storage, None, None, Order::Ascending) {
+        let (creator, listing_id) = item.unwrap();
+        let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+        prop_assert_eq!(&creator, &a_listing.creator);
+        prop_assert!(
+            a_listing.status != BidStatus::Rejected && !a_listing.proceeds_claimed,
+            "listing {} has no proceeds left for {}",
+            listing_id,
+            creator
+        );
+    }
+    for item in LEADING_BIDS.keys(&deps.storage, None, None, Order::Ascending) {
+        let (bidder, listing_id) = item.unwrap();
+        let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+        prop_assert_eq!(&bidder, &a_listing.highest_bidder);
+        prop_assert!(
+            a_listing.status != BidStatus::Rejected && !a_listing.nft_claimed,
+            "listing {} has no NFT left for {}",
+            listing_id,
+            bidder
         );
     }
 
-    fn assert_cast_vote_success(
-        voter: &str,
-        weight: u128,
-        listing_id: u64,
-        handle_res: HandleResponse,
-    ) {
-        assert_eq!(
-            handle_res.log,
-            vec![
-                log("action", "vote_casted"),
-                log("listing_id", listing_id.to_string()),
-                log("weight", weight.to_string()),
-                log("voter", voter),
-            ]
+    for listing_id in 1..=state.listing_count {
+        let a_listing = LISTINGS.load(&deps.storage, listing_id).unwrap();
+
+        // bids must strictly increase, so only one can match the highest
+        let winners = a_listing
+            .bidders_info
+            .iter()
+            .filter(|info| info.price == a_listing.highest_bid)
+            .count();
+        prop_assert!(winners <= 1, "listing {} has {} winners", listing_id, winners);
+        if a_listing.kind == ListingKind::Auction && a_listing.status == BidStatus::Passed {
+            prop_assert_eq!(1, winners);
+            prop_assert!(a_listing.bidders.contains(&a_listing.highest_bidder));
+        }
+
+        // an NFT not yet claimed from a listing is escrowed for exactly that listing
+        let escrowed = ESCROWED_NFTS
+            .may_load(&deps.storage, (&a_listing.denom, &a_listing.token_id))
+            .unwrap();
+        if !a_listing.nft_claimed {
+            prop_assert_eq!(Some(listing_id), escrowed);
+        } else {
+            prop_assert_ne!(Some(listing_id), escrowed);
+        }
+    }
+    // the running totals of Stats agree with a full recount
+    let stats = STATS.load(&deps.storage).unwrap();
+    let listings: Vec<_> = (1..=state.listing_count)
+        .map(|listing_id| LISTINGS.load(&deps.storage, listing_id).unwrap())
+        .collect();
+    for status in [
+        BidStatus::Scheduled,
+        BidStatus::InProgress,
+        BidStatus::Tally,
+        BidStatus::Passed,
+        BidStatus::Rejected,
+    ]
+    .iter()
+    {
+        let count = listings.iter().filter(|a_listing| &a_listing.status == status).count();
+        prop_assert_eq!(count as u64, stats.status_count(status), "{:?} listings", status);
+    }
+    let sold: Vec<_> = listings
+        .iter()
+        .filter(|a_listing| a_listing.status == BidStatus::Passed)
+        .collect();
+    let volume: u128 = sold.iter().map(|a_listing| a_listing.highest_bid.u128()).sum();
+    let recorded_volume: u128 = stats.volume.iter().map(|coin| coin.amount.u128()).sum();
+    prop_assert_eq!(volume + offer_volume, recorded_volume);
+    prop_assert_eq!(sold.len() as u64 + offer_sales, stats.sales);
+
+    // the Stats query counts listings by the status the Listing query reports
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::Stats {}).unwrap();
+    let value: StatsResponse = from_binary(&res).unwrap();
+    for (status, count) in value.listings_by_status.iter() {
+        let recount = listings.iter().filter(|a_listing| &a_listing.status_at(&env.block) == status).count();
+        prop_assert_eq!(recount as u64, *count, "{:?} listings at the block", status);
+    }
+    let bidders: std::collections::BTreeSet<_> =
+        listings.iter().flat_map(|a_listing| a_listing.bidders.iter()).collect();
+    prop_assert_eq!(bidders.len() as u64, stats.unique_bidders);
+    prop_assert_eq!(
+        stats.unique_bidders as usize,
+        BIDDERS.keys(&deps.storage, None, None, Order::Ascending).count()
+    );
+
+    for item in ESCROWED_NFTS.range(&deps.storage, None, None, Order::Ascending) {
+        let ((denom, token_id), listing_id) = item.unwrap();
+        prop_assert_eq!(
+            Some(MOCK_CONTRACT_ADDR.to_string()),
+            nft_owner(&denom, &token_id),
+            "{}/{} is escrowed but not held by the contract",
+            denom,
+            token_id
         );
+        let a_listing = LISTINGS.may_load(&deps.storage, listing_id).unwrap();
+        prop_assert!(
+            a_listing.is_some_and(|a_listing| !a_listing.nft_claimed
+                && a_listing.denom == denom
+                && a_listing.token_id == token_id),
+            "{}/{} is escrowed for listing {} which does not hold it",
+            denom,
+            token_id,
+            listing_id
+        );
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn balances_are_conserved(actions in prop::collection::vec(action_strategy(), 1..40)) {
+        let mut deps = mock_deps();
+        mock_init(&mut deps);
+        let mut deposited = 0u128;
+        let mut withdrawn = 0u128;
+
+        let mut env = mock_env();
+
+        for action in actions.iter() {
+            if let Action::AdvanceBlocks { blocks } = *action {
+                env = mock_env_height(env.block.height + blocks, env.block.time.seconds() + blocks * 5);
+            }
+            let before = snapshot(&deps.storage);
+            match run_action(&mut deps, &env, action) {
+                Ok(res) => {
+                    deposited += sent_amount(action);
+                    withdrawn += withdrawn_amount(&res);
+                    apply_nft_transfers(&res)?;
+                }
+                Err(_) => deps.storage = restore(before),
+            }
+
+            let state = CONFIG.load(&deps.storage).unwrap();
+            let mut total_balance = 0u128;
+            for user in FUZZ_USERS.iter() {
+                let token_manager = BANK
+                    .may_load(&deps.storage, &Addr::unchecked(*user))
+                    .unwrap()
+                    .unwrap_or_default();
+                // every open bid is backed by tokens of its own, so each of them can settle
+                let locked: u128 = token_manager.locked_tokens.iter().map(|(_, amount)| amount.u128()).sum();
+                prop_assert!(locked <= token_manager.token_balance.u128(), "{} locks more than it holds", user);
+                total_balance += token_manager.token_balance.u128();
+            }
+            // settled winning bids are held outside of any balance until claimed,
+            let mut unclaimed_proceeds = 0u128;
+            for listing_id in 1..=state.listing_count {
+                let a_listing = LISTINGS.load(&deps.storage, listing_id)
+                    .unwrap();
+                if a_listing.status == BidStatus::Passed && !a_listing.proceeds_claimed {
+                    unclaimed_proceeds += a_listing.highest_bid.u128();
+                }
+            }
+            // as are the prices of open offers
+            let mut open_offers = 0u128;
+            for offer_id in 1..=state.offer_count {
+                if let Some(an_offer) = OFFERS.may_load(&deps.storage, offer_id).unwrap() {
+                    open_offers += an_offer.price.u128();
+                }
+            }
+            for offer_id in 1..=state.collection_offer_count {
+                if let Some(an_offer) = COLLECTION_OFFERS.may_load(&deps.storage, offer_id).unwrap() {
+                    open_offers += an_offer.price.u128() * u128::from(an_offer.quantity);
+                }
+            }
+            prop_assert_eq!(total_balance, state.staked_tokens.u128());
+            prop_assert_eq!(
+                deposited - withdrawn,
+                state.staked_tokens.u128() + unclaimed_proceeds + open_offers
+            );
+        }
+    }
+
+    // panics anywhere, contract or query, fail the case like a broken invariant
+    #[test]
+    fn invariants_hold_after_every_action(actions in prop::collection::vec(action_strategy(), 1..60)) {
+        let mut deps = mock_deps();
+        mock_init(&mut deps);
+        let mut env = mock_env();
+        let mut offer_volume = 0u128;
+        let mut offer_sales = 0u64;
+
+        for action in actions.iter() {
+            if let Action::AdvanceBlocks { blocks } = *action {
+                env = mock_env_height(env.block.height + blocks, env.block.time.seconds() + blocks * 5);
+            }
+            let before = snapshot(&deps.storage);
+            let (price, sales) = offer_sale(&deps, action);
+            match run_action(&mut deps, &env, action) {
+                Ok(res) => {
+                    apply_nft_transfers(&res)?;
+                    offer_volume += price;
+                    offer_sales += sales;
+                }
+                Err(_) => deps.storage = restore(before),
+            }
+            check_invariants(&deps, &env, offer_volume, offer_sales)?;
+        }
     }
 }
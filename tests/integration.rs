@@ -1,899 +1,410 @@
-//! This integration test tries to run and call the generated wasm.
+//! This integration test runs the auction contract compiled to Wasm inside cosmwasm-vm.
 //! It depends on a Wasm build being available, which you can create with `cargo wasm`.
 //! Then running `cargo integration-test` will validate we can properly call into that generated Wasm.
 //!
-//! You can easily convert unit tests to integration tests as follows:
-//! 1. Copy them over verbatim
-//! 2. Then change
-//!      let mut deps = mock_dependencies(20, &[]);
-//!    to
-//!      let mut deps = mock_instance(WASM, &[]);
-//!      // now you don't mock_init anymore
-//! 3. If you access raw storage, where ever you see something like:
-//!      deps.storage.get(CONFIG_KEY).expect("no data stored");
-//!    replace it with:
-//!      deps.with_storage(|store| {
-//!          let data = store.get(CONFIG_KEY).expect("no data stored");
-//!          //...
-//!      });
-//! 4. Anywhere you see query(&deps, ...) you must replace it with query(&mut deps, ...)
-
+//! cosmwasm-vm 1.0 and the allocator of cosmwasm-std 1.0 predate current toolchains: wasmer 2
+//! no longer links against recent std, and newer Wasm std builds abort on the buffer sizes
+//! cosmwasm-std 1.0 hands back. Build and run both with Rust 1.73, against the versions
+//! pinned in Cargo.lock: `cargo +1.73.0 wasm && cargo +1.73.0 integration-test`. Point the
+//! test run at its own `CARGO_TARGET_DIR` when `target/` holds stable-built crates (see Developing.md).
+//!
+//! The unit tests in `src/tests.rs` translate to this file as follows:
+//! 1. `mock_deps()` becomes `mock_auction()`, which also instantiates the contract
+//! 2. `execute(deps.as_mut(), ...)` becomes `execute(&mut instance, ...)` and returns a
+//!    `ContractResult`, so use `.unwrap()` / `.unwrap_err()` instead of matching `StdError`
+//! 3. `query(deps.as_ref(), ...)` becomes `query(&mut instance, ...)`
+#![cfg(feature = "integration")]
+
+use auction::cosmos_msg::{
+    ApprovedResponse, NftChainMsg, NftMsg, NftQuery, OwnerResponse, TokenUriResponse,
+};
+use auction::msg::{
+    AccountResponse, CreateListingResponse, ExecuteMsg, InstantiateMsg, ListingResponse,
+    QueryMsg, TokenStakeResponse,
+};
+use auction::state::{BidStatus, State};
 use cosmwasm_std::{
-    coins, from_binary, log, BankMsg, CosmosMsg, Env, HandleResponse, HandleResult, HumanAddr,
-    InitResponse, StdError, Uint128,
+    coins, from_binary, to_binary, BankMsg, ContractResult, CosmosMsg, Env, Response, SubMsg,
+    SystemResult, Uint128,
 };
-use cosmwasm_storage::to_length_prefixed;
-use cosmwasm_vm::testing::{handle, init, mock_env, mock_instance, query};
-use cosmwasm_vm::{from_slice, Api, Storage};
-use cw_voting::contract::VOTING_TOKEN;
-use cw_voting::msg::{HandleMsg, InitMsg, ListingResponse, QueryMsg};
-use cw_voting::state::State;
+use cosmwasm_vm::testing::{
+    execute, instantiate, mock_env, mock_info, query, MockApi, MockQuerier, MockStorage,
+    MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_vm::{Backend, Instance, InstanceOptions};
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 // This line will test the output of cargo wasm
-static WASM: &[u8] = include_bytes!("../target/wasm32-unknown-unknown/release/cw_voting.wasm");
+static WASM: &[u8] = include_bytes!("../target/wasm32-unknown-unknown/release/auction.wasm");
 // You can uncomment this line instead to test productionified build from rust-optimizer
 // static WASM: &[u8] = include_bytes!("../contract.wasm");
 
-const DEFAULT_END_HEIGHT: u64 = 100800u64;
+// spent across all calls on an instance, so it has to cover a whole test
+const GAS_LIMIT: u64 = 10_000_000_000_000;
+const TOKEN_DENOM: &str = "token";
+const NFT_DENOM: &str = "ethnft";
+const NFT_ID: &str = "0x42447730b00Ea074121f2429a0654A10597e3a25";
 const TEST_CREATOR: &str = "creator";
-const TEST_VOTER: &str = "voter1";
-const TEST_VOTER_2: &str = "voter2";
+const TEST_BIDDER: &str = "bidder1";
+const TEST_BIDDER_2: &str = "bidder2";
+const DEFAULT_END_HEIGHT: u64 = 100_800;
 
-fn mock_env_height(signer: &HumanAddr, height: u64, time: u64) -> Env {
-    let mut env = mock_env(signer, &[]);
-    env.block.height = height;
-    env.block.time = time;
-    env
-}
+type AuctionInstance = Instance<MockApi, MockStorage, MockQuerier<NftQuery>>;
 
-fn init_msg() -> InitMsg {
-    InitMsg {
-        denom: String::from(VOTING_TOKEN),
-    }
+thread_local! {
+    // the NFT module's ledger of NFT_DENOM tokens, id -> owner, as the mock querier reports it
+    static NFT_OWNERS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    // id -> the operator its owner approved, until the token changes hands
+    static NFT_APPROVALS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
 }
 
-fn address(index: u8) -> HumanAddr {
-    match index {
-        0 => HumanAddr(TEST_CREATOR.to_string()), // contract initializer
-        1 => HumanAddr(TEST_VOTER.to_string()),
-        2 => HumanAddr(TEST_VOTER_2.to_string()),
-        _ => panic!("Unsupported address index"),
-    }
+fn mint_nft(id: &str, owner: &str) {
+    NFT_APPROVALS.with(|approvals| approvals.borrow_mut().remove(id));
+    NFT_OWNERS.with(|owners| owners.borrow_mut().insert(id.to_string(), owner.to_string()));
 }
 
-#[test]
-fn proper_initialization() {
-    let mut deps = mock_instance(WASM, &[]);
-
-    let msg = init_msg();
-    let env = mock_env(
-        &HumanAddr(TEST_CREATOR.to_string()),
-        &coins(2, VOTING_TOKEN),
-    );
-    let res: InitResponse = init(&mut deps, env, msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let api = deps.api;
-
-    deps.with_storage(|store| {
-        let config_key_raw = to_length_prefixed(b"config");
-        let state: State = from_slice(&store.get(&config_key_raw).0.unwrap().unwrap()).unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: api
-                    .canonical_address(&HumanAddr::from(&HumanAddr(TEST_CREATOR.to_string())))
-                    .0
-                    .unwrap(),
-                listing_count: 0,
-                staked_tokens: Uint128::zero(),
-            }
-        );
-        Ok(())
-    })
-    .unwrap();
+// the owner approves the contract to transfer NFT `id`, as they would with the NFT module
+fn approve_nft(id: &str) {
+    NFT_APPROVALS.with(|approvals| {
+        approvals.borrow_mut().insert(id.to_string(), MOCK_CONTRACT_ADDR.to_string())
+    });
 }
 
-#[test]
-fn listing_not_found() {
-    let mut deps = mock_instance(WASM, &[]);
-
-    let res = query(&mut deps, QueryMsg::Listing { listing_id: 1 });
-
-    match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Listing does not exist"),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-        _ => panic!("Must return error"),
-    }
+fn nft_approved(id: &str, operator: &str) -> bool {
+    NFT_APPROVALS.with(|approvals| approvals.borrow().get(id).map(String::as_str) == Some(operator))
 }
 
-#[test]
-fn fails_create_listing_invalid_quorum_percentage() {
-    let mut deps = mock_instance(WASM, &[]);
-    let env = mock_env("voter", &coins(11, VOTING_TOKEN));
-
-    let msg = create_listing_msg(101, "test".to_string(), None, None);
-
-    let res: HandleResult = handle(&mut deps, env, msg);
+fn nft_owner(id: &str) -> Option<String> {
+    NFT_OWNERS.with(|owners| owners.borrow().get(id).cloned())
+}
 
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => {
-            assert_eq!(msg, "quorum_percentage must be 0 to 100")
+// applies the NFT transfers of a successful transaction to the ledger, as the chain does
+fn apply_nft_transfers(res: &Response<NftChainMsg>) {
+    for sub_msg in &res.messages {
+        match &sub_msg.msg {
+            CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::Transfer {
+                sender,
+                recipient,
+                id,
+                ..
+            })) => {
+                assert_eq!(Some(sender.clone()), nft_owner(id), "{} moved by a non-owner", id);
+                mint_nft(id, recipient);
+            }
+            CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::TransferFrom {
+                sender,
+                owner,
+                recipient,
+                id,
+                ..
+            })) => {
+                assert_eq!(Some(owner.clone()), nft_owner(id), "{} moved from a non-owner", id);
+                assert!(nft_approved(id, sender), "{} moved without approval", id);
+                mint_nft(id, recipient);
+            }
+            _ => {}
         }
-        Err(e) => panic!("Unexpected error: {:?}", e),
     }
 }
 
-#[test]
-fn fails_create_listing_invalid_description() {
-    let mut deps = mock_instance(WASM, &[]);
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
+// an instantiated contract with an empty NFT ledger
+fn mock_auction() -> AuctionInstance {
+    NFT_OWNERS.with(|owners| owners.borrow_mut().clear());
+    NFT_APPROVALS.with(|approvals| approvals.borrow_mut().clear());
+    let querier = MockQuerier::<NftQuery>::new(&[]).with_custom_handler(|query| {
+        let res = match query {
+            NftQuery::Owner { id, .. } => match nft_owner(id) {
+                Some(owner) => to_binary(&OwnerResponse { owner }),
+                None => return SystemResult::Ok(ContractResult::Err("NFT not found".to_string())),
+            },
+            NftQuery::TokenUri { denom, id } => to_binary(&TokenUriResponse {
+                token_uri: Some(format!("ipfs://{}/{}", denom, id)),
+            }),
+            NftQuery::Approved { id, operator, .. } => to_binary(&ApprovedResponse {
+                approved: nft_approved(id, operator),
+            }),
+        };
+        SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+    });
+    let backend = Backend {
+        api: MockApi::default(),
+        storage: MockStorage::default(),
+        querier,
+    };
+    let options = InstanceOptions {
+        gas_limit: GAS_LIMIT,
+        print_debug: false,
+    };
+    let mut instance = Instance::from_code(WASM, backend, options, None).unwrap();
 
-    let msg: HandleMsg = create_listing_msg(30, "a".to_string(), None, None);
+    let msg = InstantiateMsg {
+        denom: TOKEN_DENOM.to_string(),
+        min_duration: None,
+        max_duration: None,
+        max_start_delay: None,
+    };
+    let res: Response<NftChainMsg> =
+        instantiate(&mut instance, mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(0, res.messages.len());
+    instance
+}
 
-    let handle_res: HandleResult = handle(&mut deps, env.clone(), msg.clone());
-    match handle_res.unwrap_err() {
-        StdError::GenericErr { msg, .. } => assert_eq!(msg, "Description too short"),
-        e => panic!("unexpected error: {:?}", e),
+fn list_msg(token_id: &str, minimum_bid: u128) -> ExecuteMsg {
+    ExecuteMsg::List {
+        denom: NFT_DENOM.to_string(),
+        token_id: token_id.to_string(),
+        minimum_bid: Uint128::from(minimum_bid),
+        start: None,
+        end: None,
+        description: "test".to_string(),
+        metadata: None,
+        reserve_commitment: None,
     }
+}
 
-    let msg = create_listing_msg(
-        100,
-        "01234567890123456789012345678901234567890123456789012345678901234".to_string(),
-        None,
-        None,
-    );
-
-    let res: HandleResult = handle(&mut deps, env.clone(), msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Description too long"),
-        Err(_) => panic!("Unknown error"),
+fn bid_msg(listing_id: u64, price: u128) -> ExecuteMsg {
+    ExecuteMsg::Bid {
+        listing_id,
+        price: Uint128::from(price),
+        refund_surplus: None,
     }
 }
 
-fn create_listing_msg(
-    quorum_percentage: u8,
-    description: String,
-    start_height: Option<u64>,
-    end_height: Option<u64>,
-) -> HandleMsg {
-    let msg = HandleMsg::CreateListing {
-        quorum_percentage: Some(quorum_percentage),
-        description,
-        start_height,
-        end_height,
-    };
-    msg
+fn close_msg(listing_id: u64) -> ExecuteMsg {
+    ExecuteMsg::CloseBid {
+        listing_id,
+        reserve: None,
+    }
 }
 
-#[test]
-fn happy_days_create_listing() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env(
-        &HumanAddr(TEST_CREATOR.to_string()),
-        &coins(2, VOTING_TOKEN),
-    );
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let quorum = 30;
-    let msg = create_listing_msg(quorum, "test".to_string(), None, Some(DEFAULT_END_HEIGHT));
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-
-    assert_create_listing_result(
-        1,
-        quorum,
-        DEFAULT_END_HEIGHT,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
+// the first block at which a listing with the default end has ended
+fn ended_env() -> Env {
+    let mut env = mock_env();
+    env.block.height += DEFAULT_END_HEIGHT;
+    env
 }
 
-#[test]
-fn create_listing_no_quorum() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env_height(&HumanAddr(TEST_CREATOR.to_string()), 0, 10000);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let quorum = 0;
-    let msg = create_listing_msg(quorum, "test".to_string(), None, None);
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_create_listing_result(
-        1,
-        quorum,
-        DEFAULT_END_HEIGHT,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
+// mints NFT `token_id` to TEST_CREATOR, who approves the contract and lists it;
+// the contract takes it into escrow
+fn list(instance: &mut AuctionInstance, token_id: &str, minimum_bid: u128) -> u64 {
+    mint_nft(token_id, TEST_CREATOR);
+    approve_nft(token_id);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res: Response<NftChainMsg> =
+        execute(instance, mock_env(), info, list_msg(token_id, minimum_bid)).unwrap();
+    apply_nft_transfers(&res);
+    assert_eq!(Some(MOCK_CONTRACT_ADDR.to_string()), nft_owner(token_id));
+    let value: CreateListingResponse = from_binary(&res.data.unwrap()).unwrap();
+    value.listing_id
 }
 
-#[test]
-fn fails_end_listing_before_end_height() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env_height(&HumanAddr(TEST_CREATOR.to_string()), 0, 10000);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = create_listing_msg(0, "test".to_string(), None, Some(10001));
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_create_listing_result(
-        1,
-        0,
-        10001,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
-
-    let res = query(&mut deps, QueryMsg::Listing { listing_id: 1 }).unwrap();
-    let value: ListingResponse = from_binary(&res).unwrap();
-    assert_eq!(Some(10001), value.end_height);
-
-    let msg = HandleMsg::EndListing { listing_id: 1 };
-
-    let handle_res: HandleResult = handle(&mut deps, env.clone(), msg);
-
-    match handle_res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Voting period has not expired."),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
+fn bid(instance: &mut AuctionInstance, bidder: &str, listing_id: u64, price: u128) {
+    let info = mock_info(bidder, &coins(price, TOKEN_DENOM));
+    let _res: Response<NftChainMsg> =
+        execute(instance, mock_env(), info, bid_msg(listing_id, price)).unwrap();
 }
 
-#[test]
-fn happy_days_end_listing() {
-    const POLL_END_HEIGHT: u64 = 1000;
-    const POLL_ID: u64 = 1;
-    let stake_amount = 1000;
-    let mut deps = mock_instance(WASM, &coins(stake_amount, VOTING_TOKEN));
-
-    let msg = init_msg();
-    let mut creator_env =
-        mock_env_height(&HumanAddr(TEST_CREATOR.to_string()), POLL_END_HEIGHT, 10000);
-    let res: InitResponse = init(&mut deps, creator_env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = create_listing_msg(
-        0,
-        "test".to_string(),
-        None,
-        Some(creator_env.block.height + 1),
-    );
-
-    let handle_res: HandleResponse = handle(&mut deps, creator_env.clone(), msg).unwrap();
-
-    assert_create_listing_result(
-        POLL_ID,
-        0,
-        creator_env.block.height + 1,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(stake_amount, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
+fn query_listing(instance: &mut AuctionInstance, listing_id: u64) -> ListingResponse {
+    let res = query(instance, mock_env(), QueryMsg::Listing { listing_id }).unwrap();
+    from_binary(&res).unwrap()
+}
 
-    let msg = HandleMsg::CastVote {
-        listing_id: POLL_ID,
-        vote: "yes".to_string(),
-        weight: Uint128::from(stake_amount),
+fn query_account(instance: &mut AuctionInstance, address: &str) -> AccountResponse {
+    let msg = QueryMsg::Account {
+        address: address.to_string(),
     };
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg).unwrap();
-
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "vote_casted"),
-            log("listing_id", POLL_ID),
-            log("weight", "1000"),
-            log("voter", TEST_VOTER),
-        ]
-    );
-    creator_env.block.height = &creator_env.block.height + 1;
-
-    let msg = HandleMsg::EndListing { listing_id: POLL_ID };
-
-    let handle_res: HandleResponse = handle(&mut deps, creator_env.clone(), msg).unwrap();
-
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "end_listing"),
-            log("listing_id", "1"),
-            log("rejected_reason", ""),
-            log("passed", "true"),
-        ]
-    );
+    from_binary(&query(instance, mock_env(), msg).unwrap()).unwrap()
 }
 
-#[test]
-fn end_listing_zero_quorum() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 1000, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    //create listing
-    let env2 = mock_env_height(&address(0), 1001, 0);
-    let msg = create_listing_msg(0, "test".to_string(), None, Some(env2.block.height));
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_create_listing_result(1, 0, 1001, 0, creator, handle_res);
-
-    //end listing
-    let msg = HandleMsg::EndListing { listing_id: 1 };
-    let handle_res: HandleResponse = handle(&mut deps, env2.clone(), msg).unwrap();
-
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "end_listing"),
-            log("listing_id", "1"),
-            log("rejected_reason", "Quorum not reached"),
-            log("passed", "false"),
-        ]
-    );
+// gas spent by a single execute call
+fn gas_used(instance: &mut AuctionInstance, env: Env, sender: &str, msg: ExecuteMsg) -> u64 {
+    let gas_before = instance.get_gas_left();
+    let _res: Response<NftChainMsg> = execute(instance, env, mock_info(sender, &[]), msg).unwrap();
+    gas_before - instance.get_gas_left()
 }
 
 #[test]
-fn end_listing_quorum_rejected() {
-    let stake_amount = 100;
-    let mut deps = mock_instance(WASM, &coins(stake_amount, VOTING_TOKEN));
-    let msg = init_msg();
-    let mut env = mock_env(TEST_CREATOR, &coins(stake_amount, VOTING_TOKEN));
-    let init_res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, init_res.messages.len());
-
-    let msg = create_listing_msg(30, "test".to_string(), None, Some(&env.block.height + 1));
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "create_listing"),
-            log("creator", &HumanAddr(TEST_CREATOR.to_string())),
-            log("listing_id", "1"),
-            log("quorum_percentage", "30"),
-            log("end_height", "12346"),
-            log("start_height", "0"),
-        ]
-    );
-
-    let msg = HandleMsg::StakeVotingTokens {};
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "yes".to_string(),
-        weight: Uint128::from(10u128),
-    };
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg).unwrap();
-
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "vote_casted"),
-            log("listing_id", "1"),
-            log("weight", "10"),
-            log("voter", TEST_CREATOR),
-        ]
-    );
-
-    let msg = HandleMsg::EndListing { listing_id: 1 };
-
-    env.block.height = &env.block.height + 2;
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "end_listing"),
-            log("listing_id", "1"),
-            log("rejected_reason", "Quorum not reached"),
-            log("passed", "false"),
-        ]
-    );
+fn proper_initialization() {
+    let mut instance = mock_auction();
+
+    let res = query(&mut instance, mock_env(), QueryMsg::Config {}).unwrap();
+    let state: State = from_binary(&res).unwrap();
+    assert_eq!(TOKEN_DENOM, state.denom);
+    assert_eq!(TEST_CREATOR, state.owner);
+    assert_eq!(0, state.listing_count);
+    assert_eq!(Uint128::zero(), state.staked_tokens);
 }
 
 #[test]
-fn end_listing_nay_rejected() {
-    let voter1_stake = 100;
-    let voter2_stake = 1000;
-    let stake_amount = 100;
-    let mut deps = mock_instance(WASM, &coins(stake_amount, VOTING_TOKEN));
-    let msg = init_msg();
-    let mut creator_env = mock_env(TEST_CREATOR, &[]);
-    let init_res: InitResponse = init(&mut deps, creator_env.clone(), msg).unwrap();
-    assert_eq!(0, init_res.messages.len());
-
-    let msg = create_listing_msg(
-        10,
-        "test".to_string(),
-        None,
-        Some(creator_env.block.height + 1),
-    );
-
-    let handle_res: HandleResponse = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
+fn list_bid_close_withdraw() {
+    let mut instance = mock_auction();
+    let listing_id = list(&mut instance, NFT_ID, 10);
+
+    bid(&mut instance, TEST_BIDDER, listing_id, 20);
+    bid(&mut instance, TEST_BIDDER_2, listing_id, 30);
+    let value = query_listing(&mut instance, listing_id);
+    assert_eq!(BidStatus::InProgress, value.status);
+    assert_eq!(Uint128::from(30u128), value.highest_bid);
+    assert_eq!(TEST_BIDDER_2, value.highest_bidder);
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res: Response<NftChainMsg> =
+        execute(&mut instance, ended_env(), info, close_msg(listing_id)).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(BidStatus::Passed, query_listing(&mut instance, listing_id).status);
+
+    // the winner pulls the NFT
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res: Response<NftChainMsg> =
+        execute(&mut instance, mock_env(), mock_info(TEST_BIDDER_2, &[]), msg).unwrap();
     assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "create_listing"),
-            log("creator", &HumanAddr(TEST_CREATOR.to_string())),
-            log("listing_id", "1"),
-            log("quorum_percentage", "10"),
-            log("end_height", "12346"),
-            log("start_height", "0"),
-        ]
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::Transfer {
+            sender: MOCK_CONTRACT_ADDR.to_string(),
+            recipient: TEST_BIDDER_2.to_string(),
+            denom: NFT_DENOM.to_string(),
+            id: NFT_ID.to_string(),
+        })))]
     );
+    apply_nft_transfers(&res);
+    assert_eq!(Some(TEST_BIDDER_2.to_string()), nft_owner(NFT_ID));
 
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(voter1_stake, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER_2, &coins(voter2_stake, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let env = mock_env(TEST_VOTER_2, &[]);
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "no".to_string(),
-        weight: Uint128::from(voter2_stake),
-    };
-    let handle_res: HandleResponse = handle(&mut deps, env, msg).unwrap();
-    assert_cast_vote_success(TEST_VOTER_2, voter2_stake, 1, handle_res);
-
-    let msg = HandleMsg::EndListing { listing_id: 1 };
-
-    creator_env.block.height = &creator_env.block.height + 2;
-    let handle_res: HandleResponse = handle(&mut deps, creator_env.clone(), msg.clone()).unwrap();
+    // the creator pulls the winning bid
+    let msg = ExecuteMsg::ClaimProceeds { listing_id };
+    let res: Response<NftChainMsg> =
+        execute(&mut instance, mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
     assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "end_listing"),
-            log("listing_id", "1"),
-            log("rejected_reason", "Threshold not reached"),
-            log("passed", "false"),
-        ]
-    );
-}
-
-#[test]
-fn fails_end_listing_before_start_height() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env(TEST_CREATOR, &[]);
-    let init_res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, init_res.messages.len());
-
-    let start_height = &env.block.height + 10;
-    let quorum_percentage = 30;
-    let msg = create_listing_msg(
-        quorum_percentage,
-        "test".to_string(),
-        Some(start_height),
-        Some(DEFAULT_END_HEIGHT),
-    );
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_create_listing_result(
-        1,
-        quorum_percentage,
-        DEFAULT_END_HEIGHT,
-        start_height,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: TEST_CREATOR.to_string(),
+            amount: coins(30, TOKEN_DENOM),
+        })]
     );
-    let msg = HandleMsg::EndListing { listing_id: 1 };
-
-    let handle_res: HandleResult = handle(&mut deps, env.clone(), msg);
-
-    match handle_res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Voting period has not started."),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
-}
-
-#[test]
-fn fails_cast_vote_not_enough_staked() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env(TEST_CREATOR, &[]);
-    let init_res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, init_res.messages.len());
-
-    let msg = create_listing_msg(0, "test".to_string(), None, Some(DEFAULT_END_HEIGHT));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_create_listing_result(
-        1,
-        0,
-        DEFAULT_END_HEIGHT,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
-
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "yes".to_string(),
-        weight: Uint128::from(1u128),
-    };
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => {
-            assert_eq!(msg, "User does not have enough staked tokens.")
-        }
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
-}
-
-#[test]
-fn happy_days_cast_vote() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let quorum_percentage = 30;
-
-    let msg = create_listing_msg(quorum_percentage, "test".to_string(), None, None);
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_create_listing_result(
-        1,
-        quorum_percentage,
-        DEFAULT_END_HEIGHT,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
-    );
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-    let weight = 10u128;
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "yes".to_string(),
-        weight: Uint128::from(weight),
-    };
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_cast_vote_success(TEST_VOTER, weight, 1, handle_res);
-}
-
-#[test]
-fn happy_days_withdraw_voting_tokens() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let staked_tokens = 11;
-    let env = mock_env(TEST_VOTER, &coins(staked_tokens, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let api = deps.api;
-    //confirm stake increased
-    deps.with_storage(|store| {
-        let config_key_raw = to_length_prefixed(b"config");
-        let state: State = from_slice(&store.get(&config_key_raw).0.unwrap().unwrap()).unwrap();
-        assert_eq!(
-            state,
-            State {
-                denom: String::from(VOTING_TOKEN),
-                owner: api
-                    .canonical_address(&HumanAddr::from(&HumanAddr(TEST_CREATOR.to_string())))
-                    .0
-                    .unwrap(),
-                listing_count: 0,
-                staked_tokens: Uint128::from(staked_tokens),
-            }
-        );
-        Ok(())
-    })
-    .unwrap();
-
-    // withdraw all stake
-    let env = mock_env(TEST_VOTER, &coins(staked_tokens, VOTING_TOKEN));
-    let msg = HandleMsg::WithdrawVotingTokens {
-        amount: Some(Uint128::from(staked_tokens)),
-    };
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    let msg = handle_res.messages.get(0).expect("no message");
 
+    // the outbid bidder's lock is released, so the whole stake can be withdrawn
+    let value = query_account(&mut instance, TEST_BIDDER);
+    assert_eq!(Uint128::from(20u128), value.available_balance);
+    let msg = ExecuteMsg::WithdrawTokens { amount: None };
+    let res: Response<NftChainMsg> =
+        execute(&mut instance, mock_env(), mock_info(TEST_BIDDER, &[]), msg).unwrap();
     assert_eq!(
-        msg,
-        &CosmosMsg::Bank(BankMsg::Send {
-            from_address: HumanAddr::from("cosmos2contract"),
-            to_address: HumanAddr::from(TEST_VOTER),
-            amount: coins(staked_tokens, VOTING_TOKEN),
-        })
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: TEST_BIDDER.to_string(),
+            amount: coins(20, TOKEN_DENOM),
+        })]
     );
 
-    // staked is reduced
-    &deps
-        .with_storage(|store| {
-            let config_key_raw = to_length_prefixed(b"config");
-            let state: State = from_slice(&store.get(&config_key_raw).0.unwrap().unwrap()).unwrap();
-            assert_eq!(
-                state,
-                State {
-                    denom: String::from(VOTING_TOKEN),
-                    owner: api
-                        .canonical_address(&HumanAddr::from(&HumanAddr(TEST_CREATOR.to_string())))
-                        .0
-                        .unwrap(),
-                    listing_count: 0,
-                    staked_tokens: Uint128::zero(),
-                }
-            );
-            Ok(())
-        })
-        .unwrap();
-}
-
-#[test]
-fn fails_withdraw_voting_tokens_no_stake() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = HandleMsg::WithdrawVotingTokens {
-        amount: Some(Uint128::from(11u128)),
-    };
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Nothing staked"),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
-}
-
-#[test]
-fn fails_withdraw_too_many_tokens() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(10, VOTING_TOKEN));
-
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let env = mock_env(TEST_VOTER, &[]);
-    let msg = HandleMsg::WithdrawVotingTokens {
-        amount: Some(Uint128::from(11u128)),
+    let msg = QueryMsg::TokenStake {
+        address: TEST_BIDDER.to_string(),
     };
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => {
-            assert_eq!(msg, "User is trying to withdraw too many tokens.")
-        }
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
+    let value: TokenStakeResponse =
+        from_binary(&query(&mut instance, mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Uint128::zero(), value.token_balance);
+    let res = query(&mut instance, mock_env(), QueryMsg::Config {}).unwrap();
+    let state: State = from_binary(&res).unwrap();
+    assert_eq!(Uint128::zero(), state.staked_tokens);
 }
 
 #[test]
-fn fails_cast_vote_twice() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let quorum_percentage = 30;
-    let msg = create_listing_msg(quorum_percentage, "test".to_string(), None, None);
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-
-    assert_create_listing_result(
-        1,
-        quorum_percentage,
-        DEFAULT_END_HEIGHT,
-        0,
-        &HumanAddr(TEST_CREATOR.to_string()),
-        handle_res,
+fn rejected_listing_returns_nft_to_creator() {
+    let mut instance = mock_auction();
+    let listing_id = list(&mut instance, NFT_ID, 50);
+    bid(&mut instance, TEST_BIDDER, listing_id, 20);
+
+    let info = mock_info(TEST_CREATOR, &[]);
+    let _res: Response<NftChainMsg> =
+        execute(&mut instance, ended_env(), info, close_msg(listing_id)).unwrap();
+    assert_eq!(BidStatus::Rejected, query_listing(&mut instance, listing_id).status);
+
+    let msg = ExecuteMsg::ClaimNft { listing_id };
+    let res: Response<NftChainMsg> =
+        execute(&mut instance, mock_env(), mock_info(TEST_CREATOR, &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Custom(NftChainMsg::Nft(NftMsg::Transfer {
+            sender: MOCK_CONTRACT_ADDR.to_string(),
+            recipient: TEST_CREATOR.to_string(),
+            denom: NFT_DENOM.to_string(),
+            id: NFT_ID.to_string(),
+        })))]
     );
-
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-    let msg = HandleMsg::StakeVotingTokens {};
-
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-
-    let weight = 1u128;
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "yes".to_string(),
-        weight: Uint128::from(weight),
-    };
-    let handle_res: HandleResponse = handle(&mut deps, env.clone(), msg).unwrap();
-    assert_cast_vote_success(TEST_VOTER, weight, 1, handle_res);
-
-    let msg = HandleMsg::CastVote {
-        listing_id: 1,
-        vote: "yes".to_string(),
-        weight: Uint128::from(weight),
-    };
-    let res: HandleResult = handle(&mut deps, env.clone(), msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "User has already voted."),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
+    apply_nft_transfers(&res);
+    assert_eq!(Some(TEST_CREATOR.to_string()), nft_owner(NFT_ID));
 }
 
 #[test]
-fn fails_cast_vote_without_listing() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let msg = HandleMsg::CastVote {
-        listing_id: 0,
-        vote: "yes".to_string(),
-        weight: Uint128::from(1u128),
-    };
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Listing does not exist"),
-        Err(e) => panic!("Unexpected error: {:?}", e),
-    }
+fn errors_surface_through_the_vm() {
+    let mut instance = mock_auction();
+    let listing_id = list(&mut instance, NFT_ID, 10);
+
+    // the NFT is held for the open listing, so the creator no longer owns it
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res: ContractResult<Response<NftChainMsg>> =
+        execute(&mut instance, mock_env(), info, list_msg(NFT_ID, 10));
+    assert!(res.unwrap_err().contains("NFT is not owned by the sender"));
+
+    // nor can anyone list an NFT they do not own
+    mint_nft("other", TEST_BIDDER);
+    let info = mock_info(TEST_CREATOR, &[]);
+    let res: ContractResult<Response<NftChainMsg>> =
+        execute(&mut instance, mock_env(), info, list_msg("other", 10));
+    assert!(res.unwrap_err().contains("NFT is not owned by the sender"));
+    assert_eq!(Some(TEST_BIDDER.to_string()), nft_owner("other"));
+
+    // and the owner has to approve the contract before it can take the NFT
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res: ContractResult<Response<NftChainMsg>> =
+        execute(&mut instance, mock_env(), info, list_msg("other", 10));
+    assert!(res.unwrap_err().contains("Contract is not approved to transfer the NFT"));
+
+    let info = mock_info(TEST_BIDDER, &[]);
+    let res: ContractResult<Response<NftChainMsg>> =
+        execute(&mut instance, mock_env(), info, bid_msg(listing_id, 20));
+    assert!(res
+        .unwrap_err()
+        .contains("User does not have enough staked tokens."));
 }
 
 #[test]
-fn happy_days_stake_voting_tokens() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let creator = &address(0);
-    let env = mock_env_height(creator, 0, 0);
-    let res: InitResponse = init(&mut deps, env.clone(), msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    let env = mock_env(TEST_VOTER, &coins(11, VOTING_TOKEN));
-
-    let msg = HandleMsg::StakeVotingTokens {};
-    let handle_res: HandleResponse = handle(&mut deps, env, msg.clone()).unwrap();
-    assert_eq!(handle_res, HandleResponse::default());
-}
-
-#[test]
-fn fails_insufficient_funds() {
-    let mut deps = mock_instance(WASM, &[]);
-    let msg = init_msg();
-    let env = mock_env(TEST_VOTER, &coins(2, VOTING_TOKEN));
-    let init_res: InitResponse = init(&mut deps, env, msg).unwrap();
-    assert_eq!(0, init_res.messages.len());
-
-    // insufficient token
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(0, VOTING_TOKEN));
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Insufficient funds sent"),
-        Err(e) => panic!("Unexpected error: {:?}", e),
+fn gas_stays_bounded_with_many_bidders() {
+    const BIDDERS: u64 = 50;
+
+    let mut instance = mock_auction();
+    let small = list(&mut instance, NFT_ID, 1);
+    bid(&mut instance, TEST_BIDDER, small, 10);
+    let close_one = gas_used(&mut instance, ended_env(), TEST_CREATOR, close_msg(small));
+
+    let crowded = list(&mut instance, "crowded", 1);
+    let mut bid_costs = vec![];
+    for i in 0..BIDDERS {
+        let bidder = format!("bidder-{}", i);
+        let price = u128::from(10 + i);
+        let info = mock_info(&bidder, &coins(price, TOKEN_DENOM));
+        let gas_before = instance.get_gas_left();
+        let _res: Response<NftChainMsg> =
+            execute(&mut instance, mock_env(), info, bid_msg(crowded, price)).unwrap();
+        bid_costs.push(gas_before - instance.get_gas_left());
     }
-}
-
-#[test]
-fn fails_staking_wrong_token() {
-    let mut deps = mock_instance(WASM, &[]);
-
-    // initialize the store
-    let msg = init_msg();
-    let env = mock_env(TEST_VOTER, &coins(2, VOTING_TOKEN));
-    let res: InitResponse = init(&mut deps, env, msg).unwrap();
-    assert_eq!(0, res.messages.len());
-
-    // wrong token
-    let msg = HandleMsg::StakeVotingTokens {};
-    let env = mock_env(TEST_VOTER, &coins(11, "play money"));
-
-    let res: HandleResult = handle(&mut deps, env, msg);
-
-    match res {
-        Ok(_) => panic!("Must return error"),
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Insufficient funds sent"),
-        Err(e) => panic!("Unexpected error: {:?}", e),
+    let close_many = gas_used(&mut instance, ended_env(), TEST_CREATOR, close_msg(crowded));
+
+    // the bidders are stored on the listing, so each bid pays for those before
+    // it: a small, steady amount per bidder on top of the first bid
+    let first_bid = bid_costs[0];
+    let per_bidder = (bid_costs[BIDDERS as usize - 1] - first_bid) / (BIDDERS - 1);
+    assert!(per_bidder < first_bid / 10, "{} gas per earlier bidder", per_bidder);
+    for (i, cost) in bid_costs.iter().enumerate() {
+        let expected = first_bid + i as u64 * per_bidder;
+        assert!(
+            cost.abs_diff(expected) < expected / 10,
+            "bid {} cost {}, expected about {}",
+            i,
+            cost,
+            expected
+        );
     }
-}
-
-// helper to confirm the expected create_listing response
-fn assert_create_listing_result(
-    listing_id: u64,
-    quorum: u8,
-    end_height: u64,
-    start_height: u64,
-    creator: &HumanAddr,
-    handle_res: HandleResponse,
-) {
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "create_listing"),
-            log("creator", creator),
-            log("listing_id", listing_id.to_string()),
-            log("quorum_percentage", quorum.to_string()),
-            log("end_height", end_height.to_string()),
-            log("start_height", start_height.to_string()),
-        ]
-    );
-}
-
-fn assert_cast_vote_success(voter: &str, weight: u128, listing_id: u64, handle_res: HandleResponse) {
-    assert_eq!(
-        handle_res.log,
-        vec![
-            log("action", "vote_casted"),
-            log("listing_id", listing_id.to_string()),
-            log("weight", weight.to_string()),
-            log("voter", voter),
-        ]
-    );
+    // settling unlocks every bidder, again at a small cost per bidder
+    assert!(close_many - close_one < (BIDDERS - 1) * first_bid / 5);
+    assert_eq!(BidStatus::Passed, query_listing(&mut instance, crowded).status);
 }
@@ -0,0 +1,39 @@
+//! Fails when the checked-in files in `./schema` no longer match the Rust types.
+//! Run `cargo schema` to regenerate them.
+use std::collections::BTreeSet;
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use auction::schema::export_schemas;
+
+fn json_files(dir: &Path) -> BTreeSet<String> {
+    read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".json"))
+        .collect()
+}
+
+#[test]
+fn checked_in_schemas_are_up_to_date() {
+    // removed on drop, so a failed assertion does not leave it behind
+    let out_dir = tempfile::tempdir().unwrap();
+    export_schemas(out_dir.path());
+
+    let checked_in_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+    let expected = json_files(out_dir.path());
+    assert_eq!(
+        expected,
+        json_files(&checked_in_dir),
+        "schema/ has missing or stale files, run `cargo schema`"
+    );
+    for name in expected.iter() {
+        let generated = read_to_string(out_dir.path().join(name)).unwrap();
+        let checked_in = read_to_string(checked_in_dir.join(name)).unwrap();
+        assert!(
+            generated == checked_in,
+            "schema/{} is out of date, run `cargo schema`",
+            name
+        );
+    }
+}